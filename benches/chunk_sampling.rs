@@ -0,0 +1,41 @@
+// Сравнение последовательного и параллельного построения чанка.
+// Запуск: cargo bench --bench chunk_sampling
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use bevy_voxel_engine::{ResolutionOfTheGrid, VoxelChunk, WorldConfig, WorldDensity, WorldSeed};
+
+const ITERATIONS: u32 = 20;
+
+fn measure(mut build: impl FnMut()) -> Duration {
+    // прогрев: тайлы рек и кэши рельефа заполняются до замера
+    build();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        build();
+    }
+    start.elapsed() / ITERATIONS
+}
+
+fn main() {
+    let density = WorldDensity::from_config(&WorldConfig {
+        seed: WorldSeed::new(1),
+        ..WorldConfig::full()
+    });
+    let chunk = VoxelChunk::new([2, 0, -3]);
+    // 32 и 64 ячейки на сторону чанка
+    for cells in [32, 64] {
+        let resolution = ResolutionOfTheGrid::new(cells + 1);
+        let serial = measure(|| {
+            let grid = chunk.sample_voxel_grid(density.function(), resolution);
+            black_box(chunk.mesh_from_grid(density.function(), &grid));
+        });
+        let parallel = measure(|| {
+            black_box(chunk.return_chunk_mesh_parallel(density.function(), resolution));
+        });
+        println!(
+            "resolution {cells}: serial {serial:?}, parallel {parallel:?}, speedup {:.2}x",
+            serial.as_secs_f64() / parallel.as_secs_f64()
+        );
+    }
+}
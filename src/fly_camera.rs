@@ -1,5 +1,7 @@
 use bevy::{input::mouse::MouseMotion, prelude::*};
 
+use crate::world::LocalUp;
+
 #[derive(Component)]
 pub struct FlyCamera {
     // The speed the FlyCamera accelerates at. Defaults to `1.0`
@@ -16,6 +18,8 @@ pub struct FlyCamera {
     pub yaw: f32,
     // The current velocity of the FlyCamera. This value is always up-to-date, enforced by [FlyCameraPlugin](struct.FlyCameraPlugin.html)
     pub velocity: Vec3,
+    // Orientation of the camera's "ground": its Y axis follows the entity's `LocalUp`, yaw and pitch are applied inside it. Defaults to identity
+    pub frame: Quat,
     // Key used to move forward. Defaults to <kbd>W</kbd>
     pub key_forward: KeyCode,
     // Key used to move back. Defaults to <kbd>S</kbd>
@@ -42,6 +46,7 @@ impl Default for FlyCamera {
             pitch: 0.0,
             yaw: 0.0,
             velocity: Vec3::ZERO,
+            frame: Quat::IDENTITY,
             key_forward: KeyCode::W,
             key_back: KeyCode::S,
             key_left: KeyCode::A,
@@ -64,7 +69,7 @@ impl Plugin for FlyCameraPlugin {
 fn mouse_motion_system(
     time: Res<Time>,
     mut mouse_motion_event_reader: EventReader<MouseMotion>,
-    mut query: Query<(&mut FlyCamera, &mut Transform)>,
+    mut query: Query<(&mut FlyCamera, &mut Transform, Option<&LocalUp>)>,
 ) {
     // записывается значение изменения местоположения мыши
     let mut delta: Vec2 = Vec2::ZERO;
@@ -76,11 +81,16 @@ fn mouse_motion_system(
         return;
     }
 
-    for (mut options, mut transform) in query.iter_mut() {
+    for (mut options, mut transform, local_up) in query.iter_mut() {
         // если перемещение мыши не доступно
         if !options.enabled {
             continue;
         }
+        // доворачиваем систему отсчета к локальному "вверх" на малый угол каждый кадр,
+        // поэтому на планете направление взгляда меняется плавно, без скачков на полюсах
+        let up = local_up.map_or(Vec3::Y, |local_up| local_up.0);
+        options.frame =
+            (Quat::from_rotation_arc(options.frame * Vec3::Y, up) * options.frame).normalize();
         // delta_seconds возвращает время в f32 от Update
         // yaw - поворот вокруг x в градусах
         options.yaw -= delta.x * options.sensitivity * time.delta_seconds();
@@ -96,7 +106,8 @@ fn mouse_motion_system(
         // rotation использует кватернионы для поворота вокруг определенной оси
         // from_axis_angle принимает нормаль вектор и угол, на который
         // будет выполнен поворот относительно нормаль-вектора
-        transform.rotation = Quat::from_axis_angle(Vec3::Y, yaw_radians)
+        transform.rotation = options.frame
+            * Quat::from_axis_angle(Vec3::Y, yaw_radians)
             * Quat::from_axis_angle(-Vec3::X, pitch_radians);
     }
 }
@@ -122,9 +133,10 @@ fn camera_movement_system(
         // вычисление ускорения, заданного вводом с клавиатуры
         // складываются направления отдельно по каждой из осей, учитывая вектор в направлении которого обзор
         let rotation = transform.rotation;
-        let accel: Vec3 = (strafe_vector(&rotation) * axis_h)
-            + (forward_walk_vector(&rotation) * axis_v)
-            + (Vec3::Y * axis_float);
+        let up = options.frame * Vec3::Y;
+        let accel: Vec3 = (strafe_vector(&rotation, up) * axis_h)
+            + (forward_walk_vector(&rotation, up) * axis_v)
+            + (up * axis_float);
         let accel: Vec3 = if accel.length() != 0.0 {
             accel.normalize() * options.accel
         } else {
@@ -167,17 +179,16 @@ fn forward_vector(rotation: &Quat) -> Vec3 {
     rotation.mul_vec3(Vec3::Z).normalize()
 }
 
-// исключает движение вверх/вниз в не зависимости от камеры
-fn forward_walk_vector(rotation: &Quat) -> Vec3 {
+// исключает движение вверх/вниз (вдоль up) в не зависимости от камеры
+fn forward_walk_vector(rotation: &Quat, up: Vec3) -> Vec3 {
     let f = forward_vector(rotation);
-    let f_flattened = Vec3::new(f.x, 0.0, f.z).normalize();
-    f_flattened
+    (f - up * f.dot(up)).normalize()
 }
 
-fn strafe_vector(rotation: &Quat) -> Vec3 {
-    // поворачивает вектор на 90 градусов, чтобы найти направление стрейфа
-    Quat::from_rotation_y(90.0f32.to_radians())
-        .mul_vec3(forward_walk_vector(rotation))
+fn strafe_vector(rotation: &Quat, up: Vec3) -> Vec3 {
+    // поворачивает вектор на 90 градусов вокруг up, чтобы найти направление стрейфа
+    Quat::from_axis_angle(up, 90.0f32.to_radians())
+        .mul_vec3(forward_walk_vector(rotation, up))
         .normalize()
 }
 
@@ -7,7 +7,22 @@ use bevy_atmosphere::prelude::*;
 use fly_camera::FlyCamera;
 pub use fly_camera::FlyCameraPlugin;
 // world
-pub use world::WorldPlugin;
+pub use world::{
+    raycast, Biome, BiomeMap, BiomeTerrain, Brush, BrushOperation, Capsule, CaveCarver,
+    CaveSettings, ChunkDelta, CrosshairTarget, CsgOperation, Cuboid, Cylinder, Dem, DemError,
+    DemLoader, DemSettings, DensityCache, DensityFunction, DomainWarpSettings, EditedDensity,
+    ErodedTerrain, ErosionSettings, Falloff, FeatureTerrain, FloatingIslandSettings,
+    FloatingOrigin, FractalSettings, Heightmap, HeightmapSampling, HeightmapSettings,
+    HeightmapTerrain, HydrologySettings, HydrologyTerrain, Intersection, LocalUp, NoiseGraph,
+    NoiseGraphDensity, NoiseGraphLoader, NoiseGraphOutput, NoiseNode, OreSettings,
+    OverhangSettings, Placement, Plane, PlanetSettings, PlanetTerrain, RawDemLayout, RawDemSample,
+    RayHit, ResolutionOfTheGrid, SculptBrush, SculptTool, Sdf, SdfDensity, SdfTerrain, SmoothUnion,
+    Sphere, StratumSettings, StructureKind, StructurePlacement, StructureProp, StructureSettings,
+    StructureTerrain, SubsurfaceMaterials, SubsurfaceSettings, Subtraction, SurfaceTerrain,
+    TerrainFeatures, TerrainScale, Torus, Translate, Union, VoxelBody, VoxelBodyBundle,
+    VoxelBodyChunk, VoxelChunk, VoxelEdits, VoxelGrid, VoxelMaterial, VoxelWorld, WorldConfig,
+    WorldDem, WorldDensity, WorldHeightmap, WorldNoiseGraph, WorldPlugin, WorldSeed,
+};
 
 pub fn camera_setup(mut comands: Commands) {
     comands.spawn((
@@ -17,5 +32,25 @@ pub fn camera_setup(mut comands: Commands) {
         },
         AtmosphereCamera::default(),
         FlyCamera::default(),
+        LocalUp::default(),
+        CrosshairTarget::default(),
     ));
+    // прицел в центре экрана: по нему выбирается рельеф для правок
+    comands.spawn(NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            left: Val::Percent(50.),
+            top: Val::Percent(50.),
+            width: Val::Px(4.),
+            height: Val::Px(4.),
+            margin: UiRect {
+                left: Val::Px(-2.),
+                top: Val::Px(-2.),
+                ..default()
+            },
+            ..default()
+        },
+        background_color: Color::WHITE.into(),
+        ..default()
+    });
 }
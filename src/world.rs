@@ -1,12 +1,72 @@
 #![allow(dead_code)]
+mod biome;
+mod caves;
 mod chunk_from_marching_cubes;
 mod data_for_marching_cubes;
+mod dem;
+mod density;
+mod density_cache;
+mod edit_history;
+mod editing;
+mod erosion;
+mod floating_origin;
+mod heightmap;
+mod hydrology;
 mod logic_of_marching_cubes;
+mod material;
+mod noise_graph;
+mod planet;
+mod random;
+mod raycast;
+mod sculpt;
+mod sdf;
+mod strata;
+mod structures;
+mod terrain_features;
+mod tiles;
+mod voxel_body;
+mod world_config;
 
-use bevy::{prelude::*, utils::hashbrown::HashMap};
-use noise::{BasicMulti, NoiseFn, Seedable, SuperSimplex};
+use std::sync::Arc;
 
-use chunk_from_marching_cubes::{ResolutionOfTheGrid, VoxelChunk};
+use bevy::{prelude::*, transform::TransformSystem};
+
+pub use biome::{Biome, BiomeMap, BiomeTerrain};
+pub use caves::{CaveCarver, CaveSettings};
+pub use chunk_from_marching_cubes::{ResolutionOfTheGrid, VoxelChunk};
+pub use dem::{Dem, DemError, DemLoader, DemSettings, RawDemLayout, RawDemSample, WorldDem};
+pub use density::{DensityFunction, SurfaceTerrain, WorldDensity};
+pub use density_cache::DensityCache;
+pub use editing::{Brush, BrushOperation, ChunkDelta, EditedDensity, VoxelEdits, VoxelWorld};
+pub use erosion::{ErodedTerrain, ErosionSettings};
+pub use floating_origin::FloatingOrigin;
+pub use heightmap::{
+    Heightmap, HeightmapSampling, HeightmapSettings, HeightmapTerrain, WorldHeightmap,
+};
+pub use hydrology::{HydrologySettings, HydrologyTerrain};
+pub use logic_of_marching_cubes::VoxelGrid;
+pub use material::VoxelMaterial;
+pub use noise_graph::{
+    FractalSettings, NoiseGraph, NoiseGraphDensity, NoiseGraphLoader, NoiseGraphOutput, NoiseNode,
+    WorldNoiseGraph,
+};
+pub use planet::{LocalUp, PlanetSettings, PlanetTerrain};
+pub use raycast::{raycast, CrosshairTarget, RayHit};
+pub use sculpt::{Falloff, SculptBrush, SculptTool};
+pub use sdf::{
+    Capsule, CsgOperation, Cuboid, Cylinder, Intersection, Plane, Sdf, SdfDensity, SdfTerrain,
+    SmoothUnion, Sphere, Subtraction, Torus, Translate, Union,
+};
+pub use strata::{OreSettings, StratumSettings, SubsurfaceMaterials, SubsurfaceSettings};
+pub use structures::{
+    Placement, StructureKind, StructurePlacement, StructureProp, StructureSettings,
+    StructureTerrain,
+};
+pub use terrain_features::{
+    DomainWarpSettings, FeatureTerrain, FloatingIslandSettings, OverhangSettings, TerrainFeatures,
+};
+pub use voxel_body::{VoxelBody, VoxelBodyBundle, VoxelBodyChunk};
+pub use world_config::{TerrainScale, WorldConfig, WorldSeed};
 pub struct WorldPlugin;
 
 impl Plugin for WorldPlugin {
@@ -19,13 +79,36 @@ impl Plugin for WorldPlugin {
             1.,
             TimerMode::Repeating,
         )))
-        .add_systems(Startup, (init_chunk_creation))
+        .init_resource::<FloatingOrigin>()
+        .init_resource::<WorldConfig>()
+        .init_resource::<WorldDensity>()
+        .init_resource::<DensityCache>()
+        .init_resource::<VoxelEdits>()
+        .init_resource::<WaterMaterial>()
+        .add_asset::<NoiseGraph>()
+        .init_asset_loader::<NoiseGraphLoader>()
+        .add_asset::<Dem>()
+        .init_asset_loader::<DemLoader>()
+        .add_systems(Startup, init_chunk_creation)
         .add_systems(
             Update,
             (
-                generate_chunk_mesh,
-                delete_chunk_mesh,
-            ),
+                noise_graph::apply_noise_graph,
+                heightmap::apply_heightmap,
+                dem::apply_dem,
+                regenerate_loaded_chunks.run_if(resource_changed::<WorldDensity>()),
+            )
+                .chain()
+                .before(generate_chunk_mesh),
+        )
+        .add_systems(Update, (generate_chunk_mesh, delete_chunk_mesh))
+        .add_systems(Update, remesh_edited_chunks.after(generate_chunk_mesh))
+        .add_systems(Update, planet::update_local_up)
+        .add_systems(Update, raycast::update_crosshair_target)
+        .add_systems(Update, voxel_body::mesh_voxel_bodies)
+        .add_systems(
+            PostUpdate,
+            floating_origin::shift_floating_origin.before(TransformSystem::TransformPropagate),
         );
     }
 }
@@ -36,6 +119,33 @@ struct ChunkGenerated;
 #[derive(Component)]
 struct ChunkNotGenerated;
 
+// разрешение сетки, с которым построен меш чанка
+#[derive(Component)]
+struct ChunkLod(ResolutionOfTheGrid);
+
+// сколько правленых чанков перестраивается за кадр
+const REMESH_PER_FRAME: usize = 16;
+
+// меш воды чанка, строится отдельно от поверхности земли
+#[derive(Component)]
+struct ChunkWater;
+
+// общий полупрозрачный материал воды всех чанков
+#[derive(Resource)]
+struct WaterMaterial(Handle<StandardMaterial>);
+
+impl FromWorld for WaterMaterial {
+    fn from_world(world: &mut World) -> Self {
+        let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+        WaterMaterial(materials.add(StandardMaterial {
+            base_color: Color::rgba(0.15, 0.35, 0.6, 0.7),
+            alpha_mode: AlphaMode::Blend,
+            perceptual_roughness: 0.1,
+            ..default()
+        }))
+    }
+}
+
 #[derive(Resource)]
 struct GreetTimer(Timer);
 
@@ -45,7 +155,14 @@ struct InitChunkTimer(Timer);
 const MAX_VIEW_DISTANCE: i64 = 3000;
 
 // потребуется распаралеливание
-fn init_chunk_creation(mut commands: Commands) {
+fn init_chunk_creation(mut commands: Commands, config: Res<WorldConfig>) {
+    // планета: только слой чанков вокруг сферы, остальное пространство пусто
+    if let Some(planet_settings) = &config.planet {
+        for coordinates in planet_settings.shell_chunks() {
+            commands.spawn((VoxelChunk::new(coordinates), ChunkNotGenerated));
+        }
+        return;
+    }
     for z in -100..=100 {
         for y in -5..=5 {
             for x in -100..=100 {
@@ -55,86 +172,82 @@ fn init_chunk_creation(mut commands: Commands) {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn generate_chunk_mesh(
     time: Res<Time>,
     mut timer: ResMut<GreetTimer>,
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    floating_origin: Res<FloatingOrigin>,
+    world_density: Res<WorldDensity>,
+    mut density_cache: ResMut<DensityCache>,
+    voxel_edits: Res<VoxelEdits>,
+    water_material: Res<WaterMaterial>,
     camera_q: Query<&GlobalTransform, With<Camera3d>>,
     mut entity_q: Query<(Entity, &mut VoxelChunk), With<ChunkNotGenerated>>,
 ) {
     if timer.0.tick(time.delta()).just_finished() {
-        let mut mesh: Mesh;
-        let super_simplex = SuperSimplex::new(0);
-
-        let rules_of_generation = |value: [f64; 3]| {
-            #[allow(dead_code)]
-            let surface_y = 0. + super_simplex.get([value[0], value[2]]) / 1.2;
-            if surface_y > value[1] / 1. {
-                0.3
-            } else {
-                -0.3
-            }
-        };
-
         let chunk_visible_in_view_distance =
             (MAX_VIEW_DISTANCE as f32 / VoxelChunk::size_chunk() as f32).floor();
-        let camera_translation = camera_q.single().translation().to_array();
+        let camera_translation = floating_origin.scene_to_world(camera_q.single().translation());
         let chunk_position_with_camera =
             VoxelChunk::get_chunk_coordinates_from_global_as_vec3(camera_translation);
 
-        for (entity, mut voxel_chunk_q) in entity_q.iter_mut()
-        {
-            match Vec3::distance_squared(
+        for (entity, mut voxel_chunk_q) in entity_q.iter_mut() {
+            let resolution = match Vec3::distance_squared(
                 chunk_position_with_camera,
                 voxel_chunk_q.coordinates_as_vec3(),
             ) {
-                val if val <= chunk_visible_in_view_distance / 4. => {
-                    mesh = voxel_chunk_q
-                        .return_chunk_mesh(rules_of_generation, ResolutionOfTheGrid::new(32));
-                    commands.entity(entity).remove::<ChunkNotGenerated>();
-                    commands.entity(entity).insert((
-                        PbrBundle {
-                            mesh: meshes.add(mesh),
-                            material: materials.add(Color::BLUE.into()),
-                            ..default()
-                        },
-                        ChunkGenerated,
-                    ));
-                }
+                // 32, 16 и 8 ячеек на чанк: точки соседних LOD совпадают,
+                // поэтому DensityCache выводит грубую сетку из подробной
+                val if val <= chunk_visible_in_view_distance / 4. => 33,
                 val if val > chunk_visible_in_view_distance / 4.
                     && val <= chunk_visible_in_view_distance / 4. * 3. =>
                 {
-                    mesh = voxel_chunk_q
-                        .return_chunk_mesh(rules_of_generation, ResolutionOfTheGrid::new(16));
-                    commands.entity(entity).remove::<ChunkNotGenerated>();
-                    commands.entity(entity).insert((
-                        PbrBundle {
-                            mesh: meshes.add(mesh),
-                            material: materials.add(Color::BLUE.into()),
-                            ..default()
-                        },
-                        ChunkGenerated,
-                    ));
+                    17
                 }
                 val if val > chunk_visible_in_view_distance / 4. * 3.
                     && val <= chunk_visible_in_view_distance =>
                 {
-                    mesh = voxel_chunk_q
-                        .return_chunk_mesh(rules_of_generation, ResolutionOfTheGrid::new(8));
-                    commands.entity(entity).remove::<ChunkNotGenerated>();
-                    commands.entity(entity).insert((
-                        PbrBundle {
-                            mesh: meshes.add(mesh),
-                            material: materials.add(Color::BLUE.into()),
-                            ..default()
-                        },
-                        ChunkGenerated,
-                    ));
+                    9
                 }
-                _ => (),
-            }
+                _ => continue,
+            };
+            let resolution = ResolutionOfTheGrid::new(resolution);
+            let voxel_grid = edited_grid(
+                &voxel_chunk_q,
+                resolution,
+                &world_density,
+                &mut density_cache,
+                &voxel_edits,
+            );
+            // цвета вершин учитывают покраску
+            let mesh = voxel_chunk_q
+                .mesh_from_grid_parallel(&voxel_edits.over(world_density.function()), &voxel_grid);
+            commands.entity(entity).remove::<ChunkNotGenerated>();
+            commands.entity(entity).insert((
+                PbrBundle {
+                    mesh: meshes.add(mesh),
+                    material: materials.add(Color::WHITE.into()),
+                    transform: Transform::from_translation(
+                        floating_origin.chunk_translation(&voxel_chunk_q),
+                    ),
+                    ..default()
+                },
+                ChunkGenerated,
+                ChunkLod(resolution),
+            ));
+            spawn_chunk_children(
+                &mut commands,
+                &mut meshes,
+                &water_material,
+                &world_density,
+                &voxel_edits,
+                entity,
+                &mut voxel_chunk_q,
+                resolution,
+            );
         }
     }
 }
@@ -143,24 +256,172 @@ fn delete_chunk_mesh(
     time: Res<Time>,
     mut timer: ResMut<GreetTimer>,
     mut commands: Commands,
+    floating_origin: Res<FloatingOrigin>,
     camera_q: Query<&GlobalTransform, With<Camera3d>>,
     entity_q: Query<(Entity, &VoxelChunk), With<ChunkGenerated>>,
 ) {
     if timer.0.tick(time.delta()).just_finished() {
         let chunk_visible_in_view_distance =
             (MAX_VIEW_DISTANCE as f32 / VoxelChunk::size_chunk() as f32).floor();
-        let camera_translation = camera_q.single().translation().to_array();
+        let camera_translation = floating_origin.scene_to_world(camera_q.single().translation());
         let chunk_position_with_camera =
             VoxelChunk::get_chunk_coordinates_from_global_as_vec3(camera_translation);
 
-        for (entity, voxel_chunk) in entity_q.iter().filter(|(_, c)| {
+        for (entity, _) in entity_q.iter().filter(|(_, c)| {
             Vec3::distance_squared(chunk_position_with_camera, c.coordinates_as_vec3())
                 > chunk_visible_in_view_distance
         }) {
             commands
                 .entity(entity)
-                .remove::<(PbrBundle, ChunkGenerated)>();
+                .remove::<(PbrBundle, ChunkGenerated, ChunkLod)>();
+            commands.entity(entity).despawn_descendants();
             commands.entity(entity).insert(ChunkNotGenerated);
         }
     }
 }
+
+// после замены функции плотности уже построенные чанки и сохраненные сетки устарели
+fn regenerate_loaded_chunks(
+    mut commands: Commands,
+    mut density_cache: ResMut<DensityCache>,
+    entity_q: Query<Entity, (With<VoxelChunk>, With<ChunkGenerated>)>,
+) {
+    for entity in entity_q.iter() {
+        commands
+            .entity(entity)
+            .remove::<(PbrBundle, ChunkGenerated, ChunkLod)>();
+        commands.entity(entity).despawn_descendants();
+        commands.entity(entity).insert(ChunkNotGenerated);
+    }
+    density_cache.clear();
+}
+
+// вода и постройки чанка с учетом правок. Вода - дочерняя сущность чанка,
+// поэтому сдвигается вместе с ним
+#[allow(clippy::too_many_arguments)]
+fn spawn_chunk_children(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    water_material: &WaterMaterial,
+    world_density: &WorldDensity,
+    voxel_edits: &VoxelEdits,
+    entity: Entity,
+    voxel_chunk: &mut VoxelChunk,
+    resolution: ResolutionOfTheGrid,
+) {
+    let edited = voxel_edits.over(world_density.function());
+    if let Some(water_mesh) = voxel_chunk.return_water_mesh(&edited, resolution) {
+        commands.entity(entity).with_children(|parent| {
+            parent.spawn((
+                PbrBundle {
+                    mesh: meshes.add(water_mesh),
+                    material: water_material.0.clone(),
+                    ..default()
+                },
+                ChunkWater,
+            ));
+        });
+    }
+    structures::spawn_props(commands, world_density, &edited, entity, voxel_chunk);
+}
+
+// процедурная сетка из кэша, поверх которой записаны правки
+fn edited_grid(
+    voxel_chunk: &VoxelChunk,
+    resolution: ResolutionOfTheGrid,
+    world_density: &WorldDensity,
+    density_cache: &mut DensityCache,
+    voxel_edits: &VoxelEdits,
+) -> Arc<VoxelGrid> {
+    let voxel_grid = density_cache.get_or_sample(voxel_chunk, world_density.function(), resolution);
+    if !voxel_edits.affects(*voxel_chunk.coordinates()) {
+        return voxel_grid;
+    }
+    let mut edited = (*voxel_grid).clone();
+    voxel_edits.apply(world_density.function(), voxel_chunk, &mut edited);
+    Arc::new(edited)
+}
+
+// перестраивает меши чанков, измененных кистями; ближние к камере первыми
+#[allow(clippy::type_complexity)]
+#[allow(clippy::too_many_arguments)]
+fn remesh_edited_chunks(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    floating_origin: Res<FloatingOrigin>,
+    world_density: Res<WorldDensity>,
+    mut density_cache: ResMut<DensityCache>,
+    mut voxel_edits: ResMut<VoxelEdits>,
+    water_material: Res<WaterMaterial>,
+    camera_q: Query<&GlobalTransform, With<Camera3d>>,
+    mut chunk_q: Query<
+        (Entity, &mut VoxelChunk, &ChunkLod, Option<&Children>),
+        With<ChunkGenerated>,
+    >,
+    chunk_child_q: Query<(), Or<(With<ChunkWater>, With<StructureProp>)>>,
+) {
+    if voxel_edits.dirty_chunks().next().is_none() {
+        return;
+    }
+    let camera_translation = floating_origin.scene_to_world(camera_q.single().translation());
+    let chunk_position_with_camera =
+        VoxelChunk::get_chunk_coordinates_from_global_as_vec3(camera_translation);
+
+    let mut queue: Vec<(Entity, [i128; 3], f32)> = chunk_q
+        .iter()
+        .filter(|(_, voxel_chunk, _, _)| voxel_edits.is_dirty(*voxel_chunk.coordinates()))
+        .map(|(entity, voxel_chunk, _, _)| {
+            let distance = Vec3::distance_squared(
+                chunk_position_with_camera,
+                voxel_chunk.coordinates_as_vec3(),
+            );
+            (entity, *voxel_chunk.coordinates(), distance)
+        })
+        .collect();
+    queue.sort_by(|(_, _, first), (_, _, second)| first.total_cmp(second));
+
+    // чанки без меша учтут правки, когда будут построены
+    let waiting: Vec<[i128; 3]> = queue
+        .iter()
+        .skip(REMESH_PER_FRAME)
+        .map(|(_, coordinates, _)| *coordinates)
+        .collect();
+    for (entity, _, _) in queue.into_iter().take(REMESH_PER_FRAME) {
+        let Ok((_, mut voxel_chunk, chunk_lod, children)) = chunk_q.get_mut(entity) else {
+            continue;
+        };
+        let resolution = chunk_lod.0;
+        let voxel_grid = edited_grid(
+            &voxel_chunk,
+            resolution,
+            &world_density,
+            &mut density_cache,
+            &voxel_edits,
+        );
+        let mesh = voxel_chunk
+            .mesh_from_grid_parallel(&voxel_edits.over(world_density.function()), &voxel_grid);
+        commands.entity(entity).insert(meshes.add(mesh));
+        // вода и постройки строятся заново по измененной плотности
+        for &child in children.into_iter().flatten() {
+            if chunk_child_q.contains(child) {
+                commands.entity(child).despawn_recursive();
+            }
+        }
+        spawn_chunk_children(
+            &mut commands,
+            &mut meshes,
+            &water_material,
+            &world_density,
+            &voxel_edits,
+            entity,
+            &mut voxel_chunk,
+            resolution,
+        );
+    }
+    let dirty: Vec<[i128; 3]> = voxel_edits.dirty_chunks().copied().collect();
+    for coordinates in dirty {
+        if !waiting.contains(&coordinates) {
+            voxel_edits.mark_clean(coordinates);
+        }
+    }
+}
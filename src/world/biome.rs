@@ -0,0 +1,173 @@
+use noise::{Fbm, MultiFractal, NoiseFn, RidgedMulti, SuperSimplex};
+
+use super::density::DensityFunction;
+use super::material::VoxelMaterial;
+use super::world_config::{TerrainScale, WorldSeed};
+
+// насколько широка переходная зона между биомами в пространстве климата
+const BLEND_WIDTH: f64 = 0.35;
+
+/// Биом определяет форму поверхности и ее материал.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Biome {
+    Plains,
+    Mountains,
+    Desert,
+    Tundra,
+}
+
+impl Biome {
+    pub const ALL: [Biome; 4] = [
+        Biome::Plains,
+        Biome::Mountains,
+        Biome::Desert,
+        Biome::Tundra,
+    ];
+
+    // (температура, влажность), в которых биом выражен сильнее всего
+    fn climate(&self) -> [f64; 2] {
+        match self {
+            Biome::Plains => [0.2, 0.4],
+            Biome::Mountains => [-0.2, -0.1],
+            Biome::Desert => [0.7, -0.6],
+            Biome::Tundra => [-0.7, 0.2],
+        }
+    }
+
+    pub fn surface_material(&self) -> VoxelMaterial {
+        match self {
+            Biome::Plains => VoxelMaterial::Grass,
+            Biome::Mountains => VoxelMaterial::Rock,
+            Biome::Desert => VoxelMaterial::Sand,
+            Biome::Tundra => VoxelMaterial::Snow,
+        }
+    }
+}
+
+/// Карта биомов: шумы температуры и влажности выбирают биом в каждой точке XZ.
+pub struct BiomeMap {
+    temperature: SuperSimplex,
+    humidity: SuperSimplex,
+    // размер области одного климата в метрах
+    biome_size: f64,
+}
+
+impl BiomeMap {
+    pub fn new(seed: WorldSeed, biome_size: f64) -> Self {
+        BiomeMap {
+            temperature: SuperSimplex::new(seed.sub_seed("temperature").noise_seed()),
+            humidity: SuperSimplex::new(seed.sub_seed("humidity").noise_seed()),
+            biome_size,
+        }
+    }
+
+    pub fn climate(&self, position: [f64; 2]) -> [f64; 2] {
+        let point = [position[0] / self.biome_size, position[1] / self.biome_size];
+        [self.temperature.get(point), self.humidity.get(point)]
+    }
+
+    // вес каждого биома из Biome::ALL, сумма весов равна 1
+    pub fn weights(&self, position: [f64; 2]) -> [f64; 4] {
+        let climate = self.climate(position);
+        let mut weights = Biome::ALL.map(|biome| {
+            let center = biome.climate();
+            let distance_squared =
+                (climate[0] - center[0]).powi(2) + (climate[1] - center[1]).powi(2);
+            (-distance_squared / (BLEND_WIDTH * BLEND_WIDTH)).exp()
+        });
+        let sum: f64 = weights.iter().sum();
+        weights.iter_mut().for_each(|weight| *weight /= sum);
+        weights
+    }
+
+    pub fn biome_at(&self, position: [f64; 2]) -> Biome {
+        let weights = self.weights(position);
+        let (index, _) = weights
+            .iter()
+            .enumerate()
+            .fold((0, f64::MIN), |best, (index, weight)| {
+                if *weight > best.1 {
+                    (index, *weight)
+                } else {
+                    best
+                }
+            });
+        Biome::ALL[index]
+    }
+}
+
+/// Рельеф из нескольких биомов с плавным смешиванием высот на границах.
+pub struct BiomeTerrain {
+    biome_map: BiomeMap,
+    hills: Fbm<SuperSimplex>,
+    ridges: RidgedMulti<SuperSimplex>,
+    dunes: SuperSimplex,
+    scale: TerrainScale,
+}
+
+impl BiomeTerrain {
+    pub fn new(seed: WorldSeed, scale: TerrainScale, biome_size: f64) -> Self {
+        BiomeTerrain {
+            biome_map: BiomeMap::new(seed, biome_size),
+            hills: Fbm::<SuperSimplex>::new(seed.sub_seed("hills").noise_seed()).set_octaves(4),
+            ridges: RidgedMulti::<SuperSimplex>::new(seed.sub_seed("ridges").noise_seed())
+                .set_octaves(5),
+            dunes: SuperSimplex::new(seed.sub_seed("dunes").noise_seed()),
+            scale,
+        }
+    }
+
+    pub fn biome_map(&self) -> &BiomeMap {
+        &self.biome_map
+    }
+
+    // высота поверхности биома в единицах шума
+    fn biome_height(&self, biome: Biome, point: [f64; 2]) -> f64 {
+        match biome {
+            Biome::Plains => self.hills.get([point[0] * 0.5, point[1] * 0.5]) * 0.2,
+            Biome::Mountains => {
+                0.4 + (self.ridges.get([point[0] * 0.35, point[1] * 0.35]) + 1.) * 0.9
+            }
+            Biome::Desert => self.dunes.get([point[0] * 1.5, point[1] * 0.8]).abs() * 0.25 - 0.1,
+            Biome::Tundra => 0.1 + self.hills.get([point[0] * 0.7, point[1] * 0.7]) * 0.3,
+        }
+    }
+
+    // высота поверхности в метрах
+    pub fn surface_height(&self, position: [f64; 2]) -> f64 {
+        let point = [
+            position[0] / self.scale.horizontal,
+            position[1] / self.scale.horizontal,
+        ];
+        let weights = self.biome_map.weights(position);
+        let height: f64 = Biome::ALL
+            .iter()
+            .zip(weights)
+            .map(|(biome, weight)| self.biome_height(*biome, point) * weight)
+            .sum();
+        height * self.scale.vertical
+    }
+}
+
+impl DensityFunction for BiomeTerrain {
+    fn sample(&self, position: [f64; 3]) -> f64 {
+        self.surface_height([position[0], position[2]]) - position[1]
+    }
+
+    fn sample_column(&self, column: [f64; 2], heights: &[f64], densities: &mut [f64]) {
+        let surface = BiomeTerrain::surface_height(self, column);
+        for (height, density) in heights.iter().zip(densities.iter_mut()) {
+            *density = surface - height;
+        }
+    }
+
+    fn material(&self, position: [f64; 3]) -> VoxelMaterial {
+        self.biome_map
+            .biome_at([position[0], position[2]])
+            .surface_material()
+    }
+
+    fn surface_height(&self, position: [f64; 2]) -> Option<f64> {
+        Some(BiomeTerrain::surface_height(self, position))
+    }
+}
@@ -0,0 +1,172 @@
+use std::sync::Arc;
+
+use noise::{Fbm, MultiFractal, NoiseFn, SuperSimplex};
+
+use super::density::DensityFunction;
+use super::material::VoxelMaterial;
+use super::world_config::WorldSeed;
+
+// штраф в метрах, который полностью подавляет пещеры вне диапазона глубин
+const OUT_OF_RANGE_PENALTY: f64 = 64.;
+
+/// Настройки генератора пещер. Все расстояния в метрах.
+#[derive(Clone, Debug)]
+pub struct CaveSettings {
+    // глубина под поверхностью, с которой начинаются пещеры
+    pub min_depth: f64,
+    // глубина, ниже которой пещер нет
+    pub max_depth: f64,
+    // ширина плавного затухания пещер у границ диапазона глубин
+    pub fade: f64,
+    // масштаб шума тоннелей-"спагетти"
+    pub tunnel_scale: f64,
+    // толщина тоннеля в единицах шума: чем больше, тем шире тоннели
+    pub tunnel_width: f64,
+    // масштаб шума больших полостей
+    pub cavern_scale: f64,
+    // порог шума полостей: чем ближе к 1, тем реже полости
+    pub cavern_threshold: f64,
+    // доля поверхности (0..1), где пещеры могут выходить наружу
+    pub surface_openings: f64,
+}
+
+impl Default for CaveSettings {
+    fn default() -> Self {
+        CaveSettings {
+            min_depth: 8.,
+            max_depth: 150.,
+            fade: 6.,
+            tunnel_scale: 48.,
+            tunnel_width: 0.08,
+            cavern_scale: 96.,
+            cavern_threshold: 0.55,
+            surface_openings: 0.15,
+        }
+    }
+}
+
+/// Вырезает тоннели и полости из рельефа, заданного другой функцией плотности.
+///
+/// Глубина отсчитывается по `DensityFunction::depth` исходного рельефа
+/// (или от y = 0, если рельеф не задан поверхностью). Там, где шум входов
+/// превышает порог `surface_openings`, верхняя граница пещер поднимается над
+/// поверхностью, и тоннели гарантированно выходят на свет.
+pub struct CaveCarver {
+    terrain: Arc<dyn DensityFunction>,
+    tunnels: [SuperSimplex; 2],
+    caverns: Fbm<SuperSimplex>,
+    entrances: SuperSimplex,
+    settings: CaveSettings,
+}
+
+impl CaveCarver {
+    pub fn new(terrain: Arc<dyn DensityFunction>, seed: WorldSeed, settings: CaveSettings) -> Self {
+        CaveCarver {
+            terrain,
+            tunnels: [
+                SuperSimplex::new(seed.sub_seed("cave_tunnels_a").noise_seed()),
+                SuperSimplex::new(seed.sub_seed("cave_tunnels_b").noise_seed()),
+            ],
+            caverns: Fbm::<SuperSimplex>::new(seed.sub_seed("caverns").noise_seed()).set_octaves(3),
+            entrances: SuperSimplex::new(seed.sub_seed("cave_entrances").noise_seed()),
+            settings,
+        }
+    }
+
+    pub fn settings(&self) -> &CaveSettings {
+        &self.settings
+    }
+
+    // 1 там, где разрешен вход в пещеру, 0 - где поверхность сплошная
+    fn entrance_mask(&self, position: [f64; 2]) -> f64 {
+        let scale = self.settings.tunnel_scale * 4.;
+        let value = self
+            .entrances
+            .get([position[0] / scale, position[1] / scale]);
+        let threshold = 1. - 2. * self.settings.surface_openings.clamp(0., 1.);
+        smoothstep(threshold, threshold + 0.1, value)
+    }
+
+    // 1 в диапазоне глубин пещер, 0 - вне его
+    fn depth_mask(&self, depth: f64, entrance: f64) -> f64 {
+        let settings = &self.settings;
+        // у входа верхняя граница поднимается выше поверхности
+        let min_depth = settings.min_depth + (-settings.fade - settings.min_depth) * entrance;
+        smoothstep(min_depth - settings.fade, min_depth, depth)
+            * (1.
+                - smoothstep(
+                    settings.max_depth,
+                    settings.max_depth + settings.fade,
+                    depth,
+                ))
+    }
+
+    // положительное значение - точка внутри пещеры, в метрах
+    pub fn cave_density(&self, position: [f64; 3]) -> f64 {
+        let settings = &self.settings;
+        let tunnel_point = position.map(|value| value / settings.tunnel_scale);
+        let radius = self.tunnels[0]
+            .get(tunnel_point)
+            .hypot(self.tunnels[1].get(tunnel_point));
+        let tunnel = (settings.tunnel_width - radius) * settings.tunnel_scale;
+
+        let cavern_point = position.map(|value| value / settings.cavern_scale);
+        let cavern = (self.caverns.get(cavern_point) - settings.cavern_threshold)
+            * settings.cavern_scale
+            * 0.5;
+
+        let depth = self.terrain.depth(position).unwrap_or(-position[1]);
+        let entrance = if settings.surface_openings > 0. {
+            self.entrance_mask([position[0], position[2]])
+        } else {
+            0.
+        };
+        let mask = self.depth_mask(depth, entrance);
+
+        tunnel.max(cavern) - (1. - mask) * OUT_OF_RANGE_PENALTY
+    }
+}
+
+impl DensityFunction for CaveCarver {
+    fn sample(&self, position: [f64; 3]) -> f64 {
+        let terrain = self.terrain.sample(position);
+        if terrain <= 0. {
+            return terrain;
+        }
+        terrain.min(-self.cave_density(position))
+    }
+
+    fn sample_column(&self, column: [f64; 2], heights: &[f64], densities: &mut [f64]) {
+        self.terrain.sample_column(column, heights, densities);
+        for (height, density) in heights.iter().zip(densities.iter_mut()) {
+            if *density > 0. {
+                *density = density.min(-self.cave_density([column[0], *height, column[1]]));
+            }
+        }
+    }
+
+    fn material(&self, position: [f64; 3]) -> VoxelMaterial {
+        self.terrain.material(position)
+    }
+
+    fn surface_height(&self, position: [f64; 2]) -> Option<f64> {
+        self.terrain.surface_height(position)
+    }
+
+    fn water_level(&self, position: [f64; 2]) -> Option<f64> {
+        self.terrain.water_level(position)
+    }
+
+    fn depth(&self, position: [f64; 3]) -> Option<f64> {
+        self.terrain.depth(position)
+    }
+
+    fn up(&self, position: [f64; 3]) -> [f64; 3] {
+        self.terrain.up(position)
+    }
+}
+
+pub(crate) fn smoothstep(edge0: f64, edge1: f64, value: f64) -> f64 {
+    let t = ((value - edge0) / (edge1 - edge0)).clamp(0., 1.);
+    t * t * (3. - 2. * t)
+}
@@ -1,12 +1,18 @@
 #![allow(dead_code)]
+use super::density::DensityFunction;
 use super::logic_of_marching_cubes::{self, VoxelGrid};
 
 use bevy::prelude::*;
 use bevy::render::mesh::{self, PrimitiveTopology};
+use rayon::prelude::*;
 
 //размер воксельного чанка в абстрактной системе счисления
 const SIZE_CHUNK: u16 = 32;
 
+// на сколько метров объем воды заходит под поверхность земли
+const WATER_OVERLAP: f64 = 1.;
+
+#[derive(Clone, Copy)]
 pub struct ResolutionOfTheGrid {
     value: u64,
 }
@@ -25,7 +31,7 @@ impl ResolutionOfTheGrid {
     }
 }
 
-#[derive(Component)]
+#[derive(Component, Default)]
 pub struct VoxelChunk {
     coordinates: [i128; 3],
 }
@@ -35,19 +41,20 @@ impl VoxelChunk {
         VoxelChunk { coordinates }
     }
 
-    pub fn get_chunk_coordinates_from_global(value: [f32; 3]) -> [i128; 3] {
+    // принимает истинные (не смещенные плавающим началом координат) мировые координаты
+    pub fn get_chunk_coordinates_from_global(value: [f64; 3]) -> [i128; 3] {
         [
-            Self::round_chunk_coordinates(value[0] / SIZE_CHUNK as f32) as i128,
-            Self::round_chunk_coordinates(value[1] / SIZE_CHUNK as f32) as i128,
-            Self::round_chunk_coordinates(value[2] / SIZE_CHUNK as f32) as i128,
+            Self::round_chunk_coordinates(value[0] / SIZE_CHUNK as f64) as i128,
+            Self::round_chunk_coordinates(value[1] / SIZE_CHUNK as f64) as i128,
+            Self::round_chunk_coordinates(value[2] / SIZE_CHUNK as f64) as i128,
         ]
     }
 
-    pub fn get_chunk_coordinates_from_global_as_vec3(value: [f32; 3]) -> Vec3 {
+    pub fn get_chunk_coordinates_from_global_as_vec3(value: [f64; 3]) -> Vec3 {
         Vec3::new(
-            Self::round_chunk_coordinates(value[0] / SIZE_CHUNK as f32),
-            Self::round_chunk_coordinates(value[1] / SIZE_CHUNK as f32),
-            Self::round_chunk_coordinates(value[2] / SIZE_CHUNK as f32),
+            Self::round_chunk_coordinates(value[0] / SIZE_CHUNK as f64) as f32,
+            Self::round_chunk_coordinates(value[1] / SIZE_CHUNK as f64) as f32,
+            Self::round_chunk_coordinates(value[2] / SIZE_CHUNK as f64) as f32,
         )
     }
 
@@ -71,48 +78,132 @@ impl VoxelChunk {
         self.coordinates = coordinates;
     }
 
+    // истинные мировые координаты угла чанка в метрах
+    pub fn world_origin(&self) -> [i64; 3] {
+        [
+            (self.coordinates[0] * SIZE_CHUNK as i128) as i64,
+            (self.coordinates[1] * SIZE_CHUNK as i128) as i64,
+            (self.coordinates[2] * SIZE_CHUNK as i128) as i64,
+        ]
+    }
+
     pub fn return_chunk_mesh(
         &mut self,
-        generation_rules_for_the_grid: impl Fn([f64; 3]) -> f64,
+        generation_rules_for_the_grid: &dyn DensityFunction,
         resolution: ResolutionOfTheGrid,
     ) -> mesh::Mesh {
-        // разрешение воксельной сетки
-        let resolution = resolution.value();
-        let resolution_size = resolution - 1;
-        let scale_of_the_step_coordinates: f64 = SIZE_CHUNK as f64 / resolution_size as f64;
+        let voxel_grid = self.sample_voxel_grid(generation_rules_for_the_grid, resolution);
+        self.mesh_from_grid(generation_rules_for_the_grid, &voxel_grid)
+    }
+
+    // то же, что return_chunk_mesh, но опрос плотности и марширование разбиты
+    // на слои по z и выполняются параллельно; результат совпадает побайтно
+    pub fn return_chunk_mesh_parallel(
+        &self,
+        generation_rules_for_the_grid: &dyn DensityFunction,
+        resolution: ResolutionOfTheGrid,
+    ) -> mesh::Mesh {
+        let voxel_grid = self.sample_voxel_grid_parallel(generation_rules_for_the_grid, resolution);
+        self.mesh_from_grid_parallel(generation_rules_for_the_grid, &voxel_grid)
+    }
 
-        let mut voxel_grid = VoxelGrid::new(resolution);
+    // меш по уже заполненной сетке, например взятой из DensityCache;
+    // функция плотности нужна только для материалов вершин
+    pub fn mesh_from_grid(
+        &self,
+        generation_rules_for_the_grid: &dyn DensityFunction,
+        voxel_grid: &VoxelGrid,
+    ) -> mesh::Mesh {
+        let positions = self.march_voxel_grid(voxel_grid);
+        let colors: Vec<[f32; 4]> = positions
+            .iter()
+            .map(|vertex| self.vertex_color(generation_rules_for_the_grid, vertex))
+            .collect();
 
-        for z in 0..resolution {
-            for y in 0..resolution {
-                for x in 0..resolution {
-                    // вот здесь заменить
-                    voxel_grid.push(generation_rules_for_the_grid([
-                        (x as f64 + self.coordinates[0] as f64 * resolution_size as f64)
-                            * scale_of_the_step_coordinates
-                            // делает неазвисимым от размера чанка
-                            / SIZE_CHUNK as f64,
-                        (y as f64 + self.coordinates[1] as f64 * resolution_size as f64)
-                            * scale_of_the_step_coordinates
-                            / SIZE_CHUNK as f64,
-                        (z as f64 + self.coordinates[2] as f64 * resolution_size as f64)
-                            * scale_of_the_step_coordinates
-                            / SIZE_CHUNK as f64,
-                    ]) as f32);
-                }
-            }
+        Self::build_mesh(positions, colors)
+    }
+
+    pub fn mesh_from_grid_parallel(
+        &self,
+        generation_rules_for_the_grid: &dyn DensityFunction,
+        voxel_grid: &VoxelGrid,
+    ) -> mesh::Mesh {
+        let resolution = voxel_grid.resolution();
+        // слои собираются в порядке z, поэтому порядок треугольников как у последовательного обхода
+        let mut positions: Vec<[f32; 3]> = (0..resolution - 1)
+            .into_par_iter()
+            .map(|z| Self::march_slice(voxel_grid, z))
+            .flatten_iter()
+            .collect();
+        self.get_transform_vertex(&mut positions, resolution - 1);
+        let colors: Vec<[f32; 4]> = positions
+            .par_iter()
+            .map(|vertex| self.vertex_color(generation_rules_for_the_grid, vertex))
+            .collect();
+
+        Self::build_mesh(positions, colors)
+    }
+
+    // цвет вершины берется из материала в ее мировой точке
+    fn vertex_color(
+        &self,
+        generation_rules_for_the_grid: &dyn DensityFunction,
+        vertex: &[f32; 3],
+    ) -> [f32; 4] {
+        let world_origin = self.world_origin();
+        generation_rules_for_the_grid
+            .material([
+                world_origin[0] as f64 + vertex[0] as f64,
+                world_origin[1] as f64 + vertex[1] as f64,
+                world_origin[2] as f64 + vertex[2] as f64,
+            ])
+            .color()
+            .as_linear_rgba_f32()
+    }
+
+    // меш объема воды чанка, None если воды в чанке нет
+    pub fn return_water_mesh(
+        &mut self,
+        generation_rules_for_the_grid: &dyn DensityFunction,
+        resolution: ResolutionOfTheGrid,
+    ) -> Option<mesh::Mesh> {
+        let water_grid = self.sample_water_grid(generation_rules_for_the_grid, resolution)?;
+        let positions = self.march_voxel_grid(&water_grid);
+        if positions.is_empty() {
+            return None;
         }
+        // цвет воды задается материалом сущности
+        let colors = vec![[1., 1., 1., 1.]; positions.len()];
+        Some(Self::build_mesh(positions, colors))
+    }
+
+    // треугольники поверхности сетки в метрах относительно угла чанка
+    fn march_voxel_grid(&self, voxel_grid: &VoxelGrid) -> Vec<[f32; 3]> {
+        // разрешение воксельной сетки
+        let resolution = voxel_grid.resolution();
+        let resolution_size = resolution - 1;
 
         let mut positions: Vec<[f32; 3]> = Vec::new();
         for z in 0..resolution - 1 {
-            for y in 0..resolution - 1 {
-                for x in 0..resolution - 1 {
-                    logic_of_marching_cubes::march_cube((x, y, z), &voxel_grid, &mut positions);
-                }
-            }
+            positions.extend(Self::march_slice(voxel_grid, z));
         }
         self.get_transform_vertex(&mut positions, resolution_size);
+        positions
+    }
+
+    // треугольники одного слоя кубов z..z+1 в координатах сетки
+    fn march_slice(voxel_grid: &VoxelGrid, z: usize) -> Vec<[f32; 3]> {
+        let resolution = voxel_grid.resolution();
+        let mut positions: Vec<[f32; 3]> = Vec::new();
+        for y in 0..resolution - 1 {
+            for x in 0..resolution - 1 {
+                logic_of_marching_cubes::march_cube((x, y, z), voxel_grid, &mut positions);
+            }
+        }
+        positions
+    }
 
+    fn build_mesh(positions: Vec<[f32; 3]>, colors: Vec<[f32; 4]>) -> mesh::Mesh {
         let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
 
         // indices for uv
@@ -125,8 +216,8 @@ impl VoxelChunk {
                 &positions[index + 1],
                 &positions[index + 2],
             );
-            normals.push(value.clone());
-            normals.push(value.clone());
+            normals.push(value);
+            normals.push(value);
             normals.push(value);
         }
 
@@ -134,6 +225,8 @@ impl VoxelChunk {
         mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
         // normals
         mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+        // colors of the materials
+        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
         // uv
         mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0., 0.]; indices_for_uv.len()]);
         // A triangle using vertices
@@ -142,34 +235,149 @@ impl VoxelChunk {
         mesh
     }
 
-    fn get_transform_vertex(&self, positions: &mut Vec<[f32; 3]>, resolution_size: usize) {
-        positions.iter_mut().for_each(|vertex| {
-            *vertex = [
-                // вершина / разрешение * размер чанка = преобразование в новые координаты, относительно нулевых глобальных
-                // преобразование в новые координаты + координаты чанка * на размер чанка = смещение к координатам чанка
-                vertex[0] / resolution_size as f32 * SIZE_CHUNK as f32 //- SIZE_CHUNK as f32 / 2.
-                    + self.coordinates[0] as f32 * SIZE_CHUNK as f32,
-                vertex[1] / resolution_size as f32 * SIZE_CHUNK as f32 //- SIZE_CHUNK as f32 / 2.
-                    + self.coordinates[1] as f32 * SIZE_CHUNK as f32,
-                vertex[2] / resolution_size as f32 * SIZE_CHUNK as f32 //- SIZE_CHUNK as f32 / 2.
-                    + self.coordinates[2] as f32 * SIZE_CHUNK as f32,
-            ]
-        });
+    // мировые координаты точки (x, y, z) воксельной сетки в метрах
+    fn sample_position(&self, x: usize, y: usize, z: usize, resolution_size: usize) -> [f64; 3] {
+        let scale_of_the_step_coordinates: f64 = SIZE_CHUNK as f64 / resolution_size as f64;
+        [
+            (x as f64 + self.coordinates[0] as f64 * resolution_size as f64)
+                * scale_of_the_step_coordinates,
+            (y as f64 + self.coordinates[1] as f64 * resolution_size as f64)
+                * scale_of_the_step_coordinates,
+            (z as f64 + self.coordinates[2] as f64 * resolution_size as f64)
+                * scale_of_the_step_coordinates,
+        ]
     }
 
-    fn round_chunk_coordinates(value: f32) -> f32 {
-        if value >= 0. {
-            value.floor()
-        } else {
-            value.floor()
+    // заполняет воксельную сетку чанка значениями функции плотности
+    pub fn sample_voxel_grid(
+        &self,
+        generation_rules_for_the_grid: &dyn DensityFunction,
+        resolution: ResolutionOfTheGrid,
+    ) -> VoxelGrid {
+        // разрешение воксельной сетки
+        let resolution = resolution.value();
+        let resolution_size = resolution - 1;
+
+        let heights = self.grid_heights(resolution_size);
+        let mut data = vec![0.; resolution * resolution * resolution];
+        for (z, slice) in data.chunks_mut(resolution * resolution).enumerate() {
+            self.sample_slice(generation_rules_for_the_grid, z, &heights, slice);
         }
+
+        VoxelGrid::from_data(resolution, data)
     }
-}
 
-impl Default for VoxelChunk {
-    fn default() -> Self {
-        VoxelChunk {
-            coordinates: [0, 0, 0],
+    // то же, что sample_voxel_grid, но слои по z заполняются параллельно
+    pub fn sample_voxel_grid_parallel(
+        &self,
+        generation_rules_for_the_grid: &dyn DensityFunction,
+        resolution: ResolutionOfTheGrid,
+    ) -> VoxelGrid {
+        let resolution = resolution.value();
+        let heights = self.grid_heights(resolution - 1);
+        let mut data = vec![0.; resolution * resolution * resolution];
+        data.par_chunks_mut(resolution * resolution)
+            .enumerate()
+            .for_each(|(z, slice)| {
+                self.sample_slice(generation_rules_for_the_grid, z, &heights, slice)
+            });
+
+        VoxelGrid::from_data(resolution, data)
+    }
+
+    // высоты точек сетки по y, одинаковые для всех столбцов
+    fn grid_heights(&self, resolution_size: usize) -> Vec<f64> {
+        (0..=resolution_size)
+            .map(|y| self.sample_position(0, y, 0, resolution_size)[1])
+            .collect()
+    }
+
+    // заполняет слой сетки с номером z (x быстрее, затем y).
+    // Функция плотности получает мировые координаты в метрах,
+    // масштаб шума задается параметрами рельефа, а не размером чанка.
+    // Плотность запрашивается столбцами по y, чтобы рельеф-поверхность
+    // считал высоту один раз на столбец
+    fn sample_slice(
+        &self,
+        generation_rules_for_the_grid: &dyn DensityFunction,
+        z: usize,
+        heights: &[f64],
+        slice: &mut [f32],
+    ) {
+        let resolution = heights.len();
+        let mut column = vec![0.; resolution];
+        for x in 0..resolution {
+            let position = self.sample_position(x, 0, z, resolution - 1);
+            generation_rules_for_the_grid.sample_column(
+                [position[0], position[2]],
+                heights,
+                &mut column,
+            );
+            for (y, density) in column.iter().enumerate() {
+                slice[x + y * resolution] = *density as f32;
+            }
         }
     }
+
+    // сетка объема воды: положительна ниже уровня воды там, где нет земли.
+    // Вода заходит в землю на WATER_OVERLAP метров, чтобы ее нижние грани
+    // не совпадали с поверхностью рельефа. None, если воды в чанке нет
+    pub fn sample_water_grid(
+        &self,
+        generation_rules_for_the_grid: &dyn DensityFunction,
+        resolution: ResolutionOfTheGrid,
+    ) -> Option<VoxelGrid> {
+        let resolution = resolution.value();
+        let resolution_size = resolution - 1;
+
+        let mut water_levels = Vec::with_capacity(resolution * resolution);
+        for z in 0..resolution {
+            for x in 0..resolution {
+                let position = self.sample_position(x, 0, z, resolution_size);
+                water_levels
+                    .push(generation_rules_for_the_grid.water_level([position[0], position[2]]));
+            }
+        }
+        if water_levels.iter().all(Option::is_none) {
+            return None;
+        }
+
+        let mut water_grid = VoxelGrid::new(resolution);
+        let mut has_water = false;
+        for z in 0..resolution {
+            for y in 0..resolution {
+                for x in 0..resolution {
+                    let position = self.sample_position(x, y, z, resolution_size);
+                    let value = match water_levels[x + z * resolution] {
+                        Some(level) if level - position[1] > -WATER_OVERLAP => (level
+                            - position[1])
+                            .min(WATER_OVERLAP - generation_rules_for_the_grid.sample(position)),
+                        Some(level) => level - position[1],
+                        None => -WATER_OVERLAP,
+                    };
+                    has_water |= value > 0.;
+                    water_grid.push(value as f32);
+                }
+            }
+        }
+
+        has_water.then_some(water_grid)
+    }
+
+    fn get_transform_vertex(&self, positions: &mut [[f32; 3]], resolution_size: usize) {
+        positions.iter_mut().for_each(|vertex| {
+            *vertex = [
+                // вершина / разрешение * размер чанка = преобразование в метры относительно угла чанка
+                // смещение к координатам чанка задается через Transform сущности (см. FloatingOrigin),
+                // чтобы вершины оставались малыми числами вдали от нуля
+                vertex[0] / resolution_size as f32 * SIZE_CHUNK as f32,
+                vertex[1] / resolution_size as f32 * SIZE_CHUNK as f32,
+                vertex[2] / resolution_size as f32 * SIZE_CHUNK as f32,
+            ]
+        });
+    }
+
+    fn round_chunk_coordinates(value: f64) -> f64 {
+        value.floor()
+    }
 }
@@ -0,0 +1,368 @@
+use std::fmt;
+use std::sync::Arc;
+
+use bevy::{
+    asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+};
+
+use super::density::WorldDensity;
+use super::heightmap::{Heightmap, HeightmapSampling, HeightmapSettings, HeightmapTerrain};
+use super::world_config::WorldConfig;
+
+#[derive(Debug)]
+pub enum DemError {
+    // в заголовке .asc нет обязательного ключа
+    MissingHeader(&'static str),
+    InvalidNumber(String),
+    // число значений не совпадает с размером сетки
+    SizeMismatch { expected: usize, found: usize },
+    // во всей сетке нет ни одного значения высоты
+    NoData,
+    // меньше двух значений по стороне: между ними нет ни одной ячейки
+    TooSmall { width: usize, height: usize },
+}
+
+impl fmt::Display for DemError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DemError::MissingHeader(key) => write!(f, "missing `{key}` in the DEM header"),
+            DemError::InvalidNumber(value) => write!(f, "invalid number `{value}` in the DEM"),
+            DemError::SizeMismatch { expected, found } => {
+                write!(f, "expected {expected} DEM samples, found {found}")
+            }
+            DemError::NoData => write!(f, "the DEM contains only NODATA samples"),
+            DemError::TooSmall { width, height } => {
+                write!(
+                    f,
+                    "a DEM needs at least 2x2 samples, found {width}x{height}"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for DemError {}
+
+/// Тип значения в бинарном DEM без заголовка.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RawDemSample {
+    U16,
+    I16,
+    F32,
+}
+
+impl RawDemSample {
+    fn size(&self) -> usize {
+        match self {
+            RawDemSample::U16 | RawDemSample::I16 => 2,
+            RawDemSample::F32 => 4,
+        }
+    }
+}
+
+/// Описание бинарного DEM: размеры сетки в файле не хранятся.
+#[derive(Clone, Debug)]
+pub struct RawDemLayout {
+    pub width: usize,
+    pub height: usize,
+    pub sample: RawDemSample,
+    pub big_endian: bool,
+    // расстояние между соседними значениями в метрах
+    pub cell_size: f64,
+    // значение, обозначающее отсутствие данных
+    pub nodata: Option<f64>,
+}
+
+/// Цифровая модель рельефа: сетка высот в реальных метрах.
+///
+/// Строки идут с севера на юг, как в ESRI ASCII; север соответствует
+/// наименьшему z мира.
+#[derive(Clone, Debug, TypeUuid, TypePath)]
+#[uuid = "b8e4d0a3-5c6f-4a21-9e7b-3f1d2c8a6e40"]
+pub struct Dem {
+    width: usize,
+    height: usize,
+    cell_size: f64,
+    // реальные координаты (восток, север) юго-западного значения сетки
+    corner: [f64; 2],
+    elevations: Vec<f64>,
+}
+
+impl Dem {
+    // пропуски NODATA заполняются наименьшей известной высотой
+    pub fn new(
+        width: usize,
+        height: usize,
+        cell_size: f64,
+        elevations: Vec<Option<f64>>,
+    ) -> Result<Self, DemError> {
+        if width < 2 || height < 2 {
+            return Err(DemError::TooSmall { width, height });
+        }
+        if elevations.len() != width * height {
+            return Err(DemError::SizeMismatch {
+                expected: width * height,
+                found: elevations.len(),
+            });
+        }
+        let lowest = elevations
+            .iter()
+            .flatten()
+            .copied()
+            .reduce(f64::min)
+            .ok_or(DemError::NoData)?;
+        Ok(Dem {
+            width,
+            height,
+            cell_size,
+            corner: [0., 0.],
+            elevations: elevations
+                .into_iter()
+                .map(|elevation| elevation.unwrap_or(lowest))
+                .collect(),
+        })
+    }
+
+    /// Читает сетку в формате ESRI ASCII (`.asc`).
+    pub fn from_esri_ascii(text: &str) -> Result<Self, DemError> {
+        let mut tokens = text.split_whitespace().peekable();
+        let mut header = std::collections::HashMap::new();
+        // заголовок - пары "ключ значение", пока первый токен не станет числом
+        while let Some(key) = tokens.peek() {
+            if key.parse::<f64>().is_ok() {
+                break;
+            }
+            let key = tokens.next().unwrap().to_ascii_lowercase();
+            let value = tokens.next().ok_or(DemError::MissingHeader("value"))?;
+            header.insert(key, parse_number(value)?);
+        }
+        let read = |key: &'static str| header.get(key).copied().ok_or(DemError::MissingHeader(key));
+        let width = read("ncols")? as usize;
+        let height = read("nrows")? as usize;
+        let cell_size = read("cellsize")?;
+        let nodata = header.get("nodata_value").copied();
+        // xllcorner - внешний угол юго-западной ячейки, ее значение в центре ячейки
+        let corner = match (header.get("xllcenter"), header.get("yllcenter")) {
+            (Some(x), Some(y)) => [*x, *y],
+            _ => [
+                header.get("xllcorner").copied().unwrap_or(0.) + cell_size / 2.,
+                header.get("yllcorner").copied().unwrap_or(0.) + cell_size / 2.,
+            ],
+        };
+
+        let elevations = tokens
+            .map(|token| parse_number(token).map(|value| (Some(value) != nodata).then_some(value)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Dem::new(width, height, cell_size, elevations)?.with_corner(corner))
+    }
+
+    /// Читает бинарный DEM из 16- или 32-битных значений без заголовка.
+    pub fn from_raw(bytes: &[u8], layout: &RawDemLayout) -> Result<Self, DemError> {
+        let sample_size = layout.sample.size();
+        if bytes.len() != layout.width * layout.height * sample_size {
+            return Err(DemError::SizeMismatch {
+                expected: layout.width * layout.height,
+                found: bytes.len() / sample_size,
+            });
+        }
+        let elevations = bytes
+            .chunks_exact(sample_size)
+            .map(|sample| {
+                let value = match (layout.sample, layout.big_endian) {
+                    (RawDemSample::U16, false) => u16::from_le_bytes([sample[0], sample[1]]) as f64,
+                    (RawDemSample::U16, true) => u16::from_be_bytes([sample[0], sample[1]]) as f64,
+                    (RawDemSample::I16, false) => i16::from_le_bytes([sample[0], sample[1]]) as f64,
+                    (RawDemSample::I16, true) => i16::from_be_bytes([sample[0], sample[1]]) as f64,
+                    (RawDemSample::F32, false) => {
+                        f32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]]) as f64
+                    }
+                    (RawDemSample::F32, true) => {
+                        f32::from_be_bytes([sample[0], sample[1], sample[2], sample[3]]) as f64
+                    }
+                };
+                (Some(value) != layout.nodata && value.is_finite()).then_some(value)
+            })
+            .collect();
+        Dem::new(layout.width, layout.height, layout.cell_size, elevations)
+    }
+
+    // задает реальные координаты (восток, север) юго-западного значения сетки
+    pub fn with_corner(mut self, corner: [f64; 2]) -> Self {
+        self.corner = corner;
+        self
+    }
+
+    pub fn corner(&self) -> [f64; 2] {
+        self.corner
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn cell_size(&self) -> f64 {
+        self.cell_size
+    }
+
+    // высота в реальных метрах, (x, y) - столбец и строка сетки
+    pub fn elevation(&self, x: usize, y: usize) -> f64 {
+        self.elevations[x + y * self.width]
+    }
+
+    pub fn elevation_range(&self) -> (f64, f64) {
+        self.elevations
+            .iter()
+            .fold((f64::MAX, f64::MIN), |(min, max), elevation| {
+                (min.min(*elevation), max.max(*elevation))
+            })
+    }
+
+    /// Переводит DEM в рельеф мира: одна единица мира соответствует
+    /// `metres_per_voxel` реальным метрам по всем осям.
+    pub fn to_terrain(&self, settings: &DemSettings) -> HeightmapTerrain {
+        let (min, max) = self.elevation_range();
+        let range = (max - min).max(f64::EPSILON);
+        let datum = settings.datum.unwrap_or(min);
+        let horizontal = 1. / settings.metres_per_voxel;
+        let vertical = horizontal * settings.vertical_exaggeration;
+        let extents = [
+            (self.width - 1) as f64 * self.cell_size * horizontal,
+            (self.height - 1) as f64 * self.cell_size * horizontal,
+        ];
+        // северо-западное значение: первая строка сетки
+        let north_west = [
+            self.corner[0],
+            self.corner[1] + (self.height - 1) as f64 * self.cell_size,
+        ];
+        // по умолчанию центр DEM ставится в начало координат мира;
+        // с geo_origin соседние DEM встают по своей привязке
+        let origin = match (settings.origin, settings.geo_origin) {
+            (Some(origin), _) => origin,
+            (None, Some(geo_origin)) => [
+                (north_west[0] - geo_origin[0]) * horizontal,
+                (geo_origin[1] - north_west[1]) * horizontal,
+            ],
+            (None, None) => [-extents[0] / 2., -extents[1] / 2.],
+        };
+        let heightmap = Heightmap::new(
+            self.width,
+            self.height,
+            self.elevations
+                .iter()
+                .map(|elevation| ((elevation - min) / range) as f32)
+                .collect(),
+        );
+        HeightmapTerrain::new(
+            heightmap,
+            HeightmapSettings {
+                origin,
+                extents,
+                base_height: (min - datum) * vertical,
+                vertical_scale: range * vertical,
+                sampling: settings.sampling,
+                border_blend: 0.,
+            },
+            None,
+        )
+    }
+}
+
+fn parse_number(value: &str) -> Result<f64, DemError> {
+    value
+        .parse()
+        .map_err(|_| DemError::InvalidNumber(value.to_string()))
+}
+
+/// Перевод реальных высот DEM в координаты мира.
+#[derive(Clone, Debug)]
+pub struct DemSettings {
+    // сколько реальных метров приходится на один метр (воксель) мира
+    pub metres_per_voxel: f64,
+    // дополнительное растяжение высот
+    pub vertical_exaggeration: f64,
+    // реальная высота, которая попадает на y = 0; None - наименьшая высота DEM
+    pub datum: Option<f64>,
+    // мировые координаты (x, z) северо-западного угла; None - DEM по центру мира
+    // или по geo_origin
+    pub origin: Option<[f64; 2]>,
+    // реальные координаты (восток, север), которые попадают в начало координат мира.
+    // DEM ставится по своей привязке, так что соседние тайлы стыкуются
+    pub geo_origin: Option<[f64; 2]>,
+    pub sampling: HeightmapSampling,
+}
+
+impl Default for DemSettings {
+    fn default() -> Self {
+        DemSettings {
+            metres_per_voxel: 1.,
+            vertical_exaggeration: 1.,
+            datum: None,
+            origin: None,
+            geo_origin: None,
+            sampling: HeightmapSampling::Bicubic,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct DemLoader;
+
+impl AssetLoader for DemLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let dem = Dem::from_esri_ascii(std::str::from_utf8(bytes)?)?;
+            load_context.set_default_asset(LoadedAsset::new(dem));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["asc"]
+    }
+}
+
+/// DEM, который используется как рельеф мира. После загрузки заменяет `WorldDensity`;
+/// слои из `WorldConfig` строятся поверх него.
+#[derive(Resource)]
+pub struct WorldDem {
+    pub dem: Handle<Dem>,
+    pub settings: DemSettings,
+}
+
+pub fn apply_dem(
+    mut asset_events: EventReader<AssetEvent<Dem>>,
+    world_dem: Option<Res<WorldDem>>,
+    dems: Res<Assets<Dem>>,
+    config: Res<WorldConfig>,
+    mut world_density: ResMut<WorldDensity>,
+) {
+    let Some(world_dem) = world_dem else {
+        asset_events.clear();
+        return;
+    };
+
+    let dem_changed = asset_events.iter().any(|event| match event {
+        AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+            *handle == world_dem.dem
+        }
+        AssetEvent::Removed { .. } => false,
+    });
+    if !dem_changed && !world_dem.is_changed() {
+        return;
+    }
+
+    if let Some(dem) = dems.get(&world_dem.dem) {
+        *world_density =
+            WorldDensity::from_base(&config, Arc::new(dem.to_terrain(&world_dem.settings)));
+    }
+}
@@ -0,0 +1,266 @@
+use std::sync::Arc;
+
+use bevy::prelude::*;
+use noise::{NoiseFn, SuperSimplex};
+
+use super::biome::BiomeTerrain;
+use super::caves::CaveCarver;
+use super::erosion::ErodedTerrain;
+use super::hydrology::HydrologyTerrain;
+use super::material::VoxelMaterial;
+use super::planet::{PlanetSettings, PlanetTerrain};
+use super::strata::SubsurfaceMaterials;
+use super::structures::{StructurePlacement, StructureTerrain};
+use super::terrain_features::FeatureTerrain;
+use super::world_config::{TerrainScale, WorldConfig, WorldSeed};
+
+/// Функция плотности, по которой строится рельеф.
+///
+/// Положительное значение означает точку внутри земли, отрицательное - воздух.
+/// Поверхность проходит там, где значение меняет знак.
+pub trait DensityFunction: Send + Sync {
+    fn sample(&self, position: [f64; 3]) -> f64;
+
+    // плотность в точках одного столбца (x, z) на высотах heights. Рельеф-поверхность
+    // переопределяет метод, чтобы считать высоту один раз на столбец, а не на каждый воксель.
+    // Результат обязан совпадать с вызовами sample для каждой точки
+    fn sample_column(&self, column: [f64; 2], heights: &[f64], densities: &mut [f64]) {
+        for (height, density) in heights.iter().zip(densities.iter_mut()) {
+            *density = self.sample([column[0], *height, column[1]]);
+        }
+    }
+
+    // материал поверхности в точке, используется для цвета вершин
+    fn material(&self, _position: [f64; 3]) -> VoxelMaterial {
+        VoxelMaterial::default()
+    }
+
+    // высота поверхности над точкой (x, z), если рельеф задан поверхностью.
+    // Some только если у поверхности плотность равна height - y в метрах:
+    // эрозия и реки сдвигают поверхность, прибавляя к плотности разницу высот
+    fn surface_height(&self, _position: [f64; 2]) -> Option<f64> {
+        None
+    }
+
+    // высота поверхности воды над точкой (x, z), если там есть море, река или озеро
+    fn water_level(&self, _position: [f64; 2]) -> Option<f64> {
+        None
+    }
+
+    // глубина точки под поверхностью в метрах (над поверхностью отрицательна);
+    // для рельефа-поверхности считается по `surface_height`
+    fn depth(&self, position: [f64; 3]) -> Option<f64> {
+        self.surface_height([position[0], position[2]])
+            .map(|surface| surface - position[1])
+    }
+
+    // единичный вектор "вверх" в точке, направлен против силы тяжести
+    fn up(&self, _position: [f64; 3]) -> [f64; 3] {
+        [0., 1., 0.]
+    }
+}
+
+impl<F> DensityFunction for F
+where
+    F: Fn([f64; 3]) -> f64 + Send + Sync,
+{
+    fn sample(&self, position: [f64; 3]) -> f64 {
+        self(position)
+    }
+}
+
+/// Функция плотности, которой пользуется `WorldPlugin` при генерации чанков.
+///
+/// Чтобы задать свой рельеф, достаточно вставить ресурс до или после добавления плагина:
+/// `app.insert_resource(WorldDensity::new(MyTerrain))`.
+#[derive(Resource, Clone)]
+pub struct WorldDensity {
+    function: Arc<dyn DensityFunction>,
+    // расстановка построек, если она есть в конвейере; по ней чанки ставят объекты
+    structures: Option<Arc<StructurePlacement>>,
+}
+
+impl WorldDensity {
+    pub fn new(density_function: impl DensityFunction + 'static) -> Self {
+        WorldDensity::from_arc(Arc::new(density_function))
+    }
+
+    pub fn from_arc(density_function: Arc<dyn DensityFunction>) -> Self {
+        WorldDensity {
+            function: density_function,
+            structures: None,
+        }
+    }
+
+    pub fn with_structures(mut self, structures: Arc<StructurePlacement>) -> Self {
+        self.structures = Some(structures);
+        self
+    }
+
+    // стандартный конвейер генерации: биомы, эрозия, реки и озера, объемные особенности рельефа,
+    // пещеры, постройки и, наконец, материалы под поверхностью
+    pub fn from_config(config: &WorldConfig) -> Self {
+        if let Some(planet_settings) = &config.planet {
+            return WorldDensity::planet_from_config(config, planet_settings);
+        }
+        WorldDensity::from_base(
+            config,
+            Arc::new(BiomeTerrain::new(
+                config.seed,
+                config.terrain_scale,
+                config.biome_size,
+            )),
+        )
+    }
+
+    // тот же конвейер поверх готового рельефа (карты высот, DEM); плотность
+    // `base` должна быть равна высоте поверхности минус y в метрах.
+    // Настройки планеты при этом не используются
+    pub fn from_base(config: &WorldConfig, base: Arc<dyn DensityFunction>) -> Self {
+        let mut density = base;
+        if let Some(erosion_settings) = &config.erosion {
+            density = Arc::new(ErodedTerrain::new(
+                density,
+                config.seed,
+                erosion_settings.clone(),
+            ));
+        }
+        if let Some(hydrology_settings) = &config.hydrology {
+            density = Arc::new(HydrologyTerrain::new(density, hydrology_settings.clone()));
+        }
+        if !config.features.is_empty() {
+            density = Arc::new(FeatureTerrain::new(
+                density,
+                config.seed,
+                config.features.clone(),
+            ));
+        }
+        if let Some(cave_settings) = &config.caves {
+            density = Arc::new(CaveCarver::new(density, config.seed, cave_settings.clone()));
+        }
+        let mut structures = None;
+        if !config.structures.is_empty() {
+            let placement = Arc::new(StructurePlacement::new(
+                density.clone(),
+                config.seed,
+                config.biome_size,
+                config.structures.clone(),
+            ));
+            density = Arc::new(StructureTerrain::new(density, placement.clone()));
+            structures = Some(placement);
+        }
+        if let Some(subsurface_settings) = &config.subsurface {
+            density = Arc::new(SubsurfaceMaterials::new(
+                density,
+                config.seed,
+                subsurface_settings.clone(),
+            ));
+        }
+        WorldDensity {
+            function: density,
+            structures,
+        }
+    }
+
+    // конвейер планеты: слои, которым нужна поверхность над плоскостью
+    // (эрозия, реки и озера, постройки), пропускаются
+    fn planet_from_config(config: &WorldConfig, planet_settings: &PlanetSettings) -> Self {
+        let mut density: Arc<dyn DensityFunction> =
+            Arc::new(PlanetTerrain::new(config.seed, planet_settings.clone()));
+        if !config.features.is_empty() {
+            density = Arc::new(FeatureTerrain::new(
+                density,
+                config.seed,
+                config.features.clone(),
+            ));
+        }
+        if let Some(cave_settings) = &config.caves {
+            density = Arc::new(CaveCarver::new(density, config.seed, cave_settings.clone()));
+        }
+        if let Some(subsurface_settings) = &config.subsurface {
+            density = Arc::new(SubsurfaceMaterials::new(
+                density,
+                config.seed,
+                subsurface_settings.clone(),
+            ));
+        }
+        WorldDensity::from_arc(density)
+    }
+
+    pub fn function(&self) -> &dyn DensityFunction {
+        self.function.as_ref()
+    }
+
+    pub fn function_arc(&self) -> Arc<dyn DensityFunction> {
+        self.function.clone()
+    }
+
+    pub fn structures(&self) -> Option<&Arc<StructurePlacement>> {
+        self.structures.as_ref()
+    }
+
+    pub fn sample(&self, position: [f64; 3]) -> f64 {
+        self.function.sample(position)
+    }
+
+    // направление "вверх" в точке мира: против силы тяжести
+    pub fn up(&self, position: [f64; 3]) -> Vec3 {
+        let up = self.function.up(position);
+        Vec3::new(up[0] as f32, up[1] as f32, up[2] as f32)
+    }
+
+    // материал в любой точке, в том числе под поверхностью - для копания и лучей
+    pub fn material(&self, position: [f64; 3]) -> VoxelMaterial {
+        self.function.material(position)
+    }
+}
+
+impl FromWorld for WorldDensity {
+    fn from_world(world: &mut World) -> Self {
+        let config = world
+            .get_resource::<WorldConfig>()
+            .cloned()
+            .unwrap_or_default();
+        WorldDensity::from_config(&config)
+    }
+}
+
+/// Простейший рельеф: одна холмистая поверхность из 2D шума.
+pub struct SurfaceTerrain {
+    super_simplex: SuperSimplex,
+    scale: TerrainScale,
+}
+
+impl SurfaceTerrain {
+    pub fn new(seed: WorldSeed, scale: TerrainScale) -> Self {
+        SurfaceTerrain {
+            super_simplex: SuperSimplex::new(seed.sub_seed("surface").noise_seed()),
+            scale,
+        }
+    }
+
+    fn surface_y(&self, position: [f64; 2]) -> f64 {
+        self.super_simplex.get([
+            position[0] / self.scale.horizontal,
+            position[1] / self.scale.horizontal,
+        ]) / 1.2
+            * self.scale.vertical
+    }
+}
+
+impl DensityFunction for SurfaceTerrain {
+    fn sample(&self, position: [f64; 3]) -> f64 {
+        self.surface_y([position[0], position[2]]) - position[1]
+    }
+
+    fn sample_column(&self, column: [f64; 2], heights: &[f64], densities: &mut [f64]) {
+        let surface = self.surface_y(column);
+        for (height, density) in heights.iter().zip(densities.iter_mut()) {
+            *density = surface - height;
+        }
+    }
+
+    fn surface_height(&self, position: [f64; 2]) -> Option<f64> {
+        Some(self.surface_y(position))
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use bevy::prelude::*;
+
+use super::chunk_from_marching_cubes::{ResolutionOfTheGrid, VoxelChunk};
+use super::density::DensityFunction;
+use super::logic_of_marching_cubes::VoxelGrid;
+
+// сколько сеток хранится по умолчанию (сетка 33^3 занимает около 140 КБ)
+const DEFAULT_CAPACITY: usize = 512;
+
+// во сколько раз сетка из кэша может быть подробнее запрошенной
+const MAX_DOWNSAMPLE_STEP: usize = 8;
+
+// чанк и разрешение его сетки
+type GridKey = ([i128; 3], usize);
+
+/// Кэш заполненных воксельных сеток чанков.
+///
+/// Перестроение меша после смены LOD, правки или обновления швов берет сетку
+/// отсюда, а не опрашивает функцию плотности заново. Сетка меньшего разрешения
+/// выводится из сохраненной сетки большего, если их точки совпадают (33 -> 17 -> 9).
+/// При переполнении вытесняется сетка, к которой дольше всего не обращались.
+#[derive(Resource)]
+pub struct DensityCache {
+    grids: HashMap<GridKey, (Arc<VoxelGrid>, u64)>,
+    // время последнего обращения -> ключ, первым идет самая давняя сетка
+    recency: BTreeMap<u64, GridKey>,
+    clock: u64,
+    capacity: usize,
+}
+
+impl DensityCache {
+    pub fn new(capacity: usize) -> Self {
+        DensityCache {
+            grids: HashMap::new(),
+            recency: BTreeMap::new(),
+            clock: 0,
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.grids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.grids.is_empty()
+    }
+
+    // сетка из кэша без опроса плотности, отмечается как недавно использованная
+    pub fn get(&mut self, coordinates: [i128; 3], resolution: usize) -> Option<Arc<VoxelGrid>> {
+        let key = (coordinates, resolution);
+        let (grid, stamp) = self.grids.get_mut(&key)?;
+        self.recency.remove(stamp);
+        self.clock += 1;
+        *stamp = self.clock;
+        self.recency.insert(self.clock, key);
+        Some(grid.clone())
+    }
+
+    pub fn insert(&mut self, coordinates: [i128; 3], resolution: usize, grid: Arc<VoxelGrid>) {
+        let key = (coordinates, resolution);
+        self.clock += 1;
+        if let Some((_, stamp)) = self.grids.insert(key, (grid, self.clock)) {
+            self.recency.remove(&stamp);
+        }
+        self.recency.insert(self.clock, key);
+        while self.grids.len() > self.capacity {
+            let Some((_, oldest)) = self.recency.pop_first() else {
+                break;
+            };
+            self.grids.remove(&oldest);
+        }
+    }
+
+    // сетка чанка: из кэша, прореживанием сохраненной сетки большего разрешения
+    // или, если ничего не подошло, опросом функции плотности
+    pub fn get_or_sample(
+        &mut self,
+        chunk: &VoxelChunk,
+        density: &dyn DensityFunction,
+        resolution: ResolutionOfTheGrid,
+    ) -> Arc<VoxelGrid> {
+        let coordinates = *chunk.coordinates();
+        let resolution = resolution.value();
+        if let Some(grid) = self.get(coordinates, resolution) {
+            return grid;
+        }
+        // подходят сетки, у которых число ячеек кратно нужному
+        let finer = (2..=MAX_DOWNSAMPLE_STEP)
+            .filter_map(|step| self.grids.get(&(coordinates, (resolution - 1) * step + 1)))
+            .find_map(|(grid, _)| grid.downsample(resolution));
+        let grid = Arc::new(finer.unwrap_or_else(|| {
+            chunk.sample_voxel_grid_parallel(density, ResolutionOfTheGrid::new(resolution as u64))
+        }));
+        self.insert(coordinates, resolution, grid.clone());
+        grid
+    }
+
+    // забывает все разрешения чанка, например после правки плотности в нем
+    pub fn invalidate(&mut self, coordinates: [i128; 3]) {
+        let recency = &mut self.recency;
+        self.grids.retain(|(key, _), (_, stamp)| {
+            let keep = *key != coordinates;
+            if !keep {
+                recency.remove(stamp);
+            }
+            keep
+        });
+    }
+
+    pub fn clear(&mut self) {
+        self.grids.clear();
+        self.recency.clear();
+    }
+}
+
+impl Default for DensityCache {
+    fn default() -> Self {
+        DensityCache::new(DEFAULT_CAPACITY)
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::BTreeMap;
+
+use super::editing::{chunks_affected, chunks_containing, VoxelEdits};
+use super::material::VoxelMaterial;
+
+// сколько шагов отмены хранится; самые старые отбрасываются
+const MAX_UNDO_STEPS: usize = 256;
+
+// обратимая команда: прежние и новые значения дельт измененных точек
+#[derive(Default)]
+pub(crate) struct EditCommand {
+    density: BTreeMap<[i64; 3], (Option<f32>, Option<f32>)>,
+    paint: BTreeMap<[i64; 3], (Option<VoxelMaterial>, Option<VoxelMaterial>)>,
+}
+
+impl EditCommand {
+    pub(crate) fn record_density(
+        &mut self,
+        point: [i64; 3],
+        before: Option<f32>,
+        after: Option<f32>,
+    ) {
+        // прежним остается значение до первого изменения точки в команде
+        self.density.entry(point).or_insert((before, after)).1 = after;
+    }
+
+    pub(crate) fn record_paint(
+        &mut self,
+        point: [i64; 3],
+        before: Option<VoxelMaterial>,
+        after: Option<VoxelMaterial>,
+    ) {
+        self.paint.entry(point).or_insert((before, after)).1 = after;
+    }
+
+    // убирает точки, которые вернулись к прежнему значению
+    fn retain_changes(&mut self) {
+        self.density.retain(|_, (before, after)| before != after);
+        self.paint.retain(|_, (before, after)| before != after);
+    }
+
+    fn is_empty(&self) -> bool {
+        self.density.is_empty() && self.paint.is_empty()
+    }
+
+    // чанки, сетки или цвета которых затрагивает команда
+    fn chunks(&self) -> impl Iterator<Item = [i128; 3]> + '_ {
+        self.density
+            .keys()
+            .flat_map(|point| chunks_affected(*point))
+            .chain(
+                self.paint
+                    .keys()
+                    .flat_map(|point| chunks_containing(*point)),
+            )
+    }
+}
+
+// стеки отмены и повтора; открытый штрих собирает правки в один шаг
+#[derive(Default)]
+pub(crate) struct EditHistory {
+    undo: Vec<EditCommand>,
+    redo: Vec<EditCommand>,
+    pub(crate) stroke: Option<EditCommand>,
+}
+
+impl VoxelEdits {
+    /// Начинает штрих: все правки до `end_stroke` отменяются одним шагом.
+    pub fn begin_stroke(&mut self) {
+        if self.history.stroke.is_none() {
+            self.history.stroke = Some(EditCommand::default());
+        }
+    }
+
+    pub fn end_stroke(&mut self) {
+        let Some(mut command) = self.history.stroke.take() else {
+            return;
+        };
+        command.retain_changes();
+        if command.is_empty() {
+            return;
+        }
+        self.history.redo.clear();
+        self.history.undo.push(command);
+        if self.history.undo.len() > MAX_UNDO_STEPS {
+            self.history.undo.remove(0);
+        }
+    }
+
+    pub fn is_stroke_open(&self) -> bool {
+        self.history.stroke.is_some()
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.history.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.history.redo.is_empty()
+    }
+
+    // отменяет последний шаг и возвращает чанки для перестройки
+    pub fn undo(&mut self) -> Vec<[i128; 3]> {
+        self.end_stroke();
+        let Some(command) = self.history.undo.pop() else {
+            return Vec::new();
+        };
+        let touched = self.restore(&command, true);
+        self.history.redo.push(command);
+        touched
+    }
+
+    pub fn redo(&mut self) -> Vec<[i128; 3]> {
+        self.end_stroke();
+        let Some(command) = self.history.redo.pop() else {
+            return Vec::new();
+        };
+        let touched = self.restore(&command, false);
+        self.history.undo.push(command);
+        touched
+    }
+
+    pub fn clear_history(&mut self) {
+        self.history = EditHistory::default();
+    }
+
+    // выполняет правку как отдельный шаг, если штрих не открыт
+    pub(crate) fn record<T>(&mut self, edit: impl FnOnce(&mut Self) -> T) -> T {
+        if self.is_stroke_open() {
+            return edit(self);
+        }
+        self.begin_stroke();
+        let result = edit(self);
+        self.end_stroke();
+        result
+    }
+
+    // записывает прежние (undo) или новые значения команды, минуя историю
+    fn restore(&mut self, command: &EditCommand, undo: bool) -> Vec<[i128; 3]> {
+        for (point, (before, after)) in &command.density {
+            self.set_override(*point, if undo { *before } else { *after });
+        }
+        for (point, (before, after)) in &command.paint {
+            self.set_paint(*point, if undo { *before } else { *after });
+        }
+        self.finish_edit(command.chunks())
+    }
+}
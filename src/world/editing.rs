@@ -0,0 +1,609 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::chunk_from_marching_cubes::{ResolutionOfTheGrid, VoxelChunk};
+use super::density::{DensityFunction, WorldDensity};
+use super::edit_history::EditHistory;
+use super::logic_of_marching_cubes::VoxelGrid;
+use super::material::VoxelMaterial;
+use super::raycast::RayHit;
+use super::sculpt::{SculptBrush, SculptTool};
+use super::sdf::{Sdf, Sphere};
+
+// ширина полосы снаружи фигуры, в которой кисть еще меняет плотность, метры:
+// чуть больше ячейки, чтобы у соседей вырезанных точек интерполяция оставалась точной.
+// Точки внутри фигуры хранятся все, иначе вырезанный объем вернулся бы к процедурному
+const BRUSH_BAND: f64 = 1.5;
+// шаг самой грубой сетки LOD (9 точек на сторону чанка)
+const COARSEST_STEP: usize = 4;
+
+/// Кисть редактирования: фигура и ее ограничивающий куб в мировых координатах.
+///
+/// Кисть меняет только точки внутри куба, поэтому он должен охватывать фигуру.
+#[derive(Clone)]
+pub struct Brush {
+    shape: Arc<dyn Sdf>,
+    min: [f64; 3],
+    max: [f64; 3],
+}
+
+impl Brush {
+    pub fn new(shape: impl Sdf + 'static, min: [f64; 3], max: [f64; 3]) -> Self {
+        Brush {
+            shape: Arc::new(shape),
+            min,
+            max,
+        }
+    }
+
+    pub fn sphere(center: [f64; 3], radius: f64) -> Self {
+        Brush::new(
+            Sphere { center, radius },
+            center.map(|value| value - radius),
+            center.map(|value| value + radius),
+        )
+    }
+
+    pub fn shape(&self) -> &dyn Sdf {
+        self.shape.as_ref()
+    }
+
+    pub fn bounds(&self) -> ([f64; 3], [f64; 3]) {
+        (self.min, self.max)
+    }
+}
+
+/// Что кисть делает с рельефом.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BrushOperation {
+    // заполнить фигуру землей
+    Add,
+    // вырезать фигуру из земли
+    Remove,
+}
+
+/// Разреженные правки одного чанка поверх процедурной плотности.
+///
+/// Ключ - точка сетки правок внутри чанка, от 0 до 31 по каждой оси. Точка на
+/// границе принадлежит только чанку, в котором она лежит по округлению вниз,
+/// поэтому соседние чанки читают одно и то же значение и швов нет.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChunkDelta {
+    // значения плотности, заменяющие процедурные
+    pub density: BTreeMap<[u8; 3], f32>,
+    // материалы, нанесенные покраской
+    pub paint: BTreeMap<[u8; 3], VoxelMaterial>,
+}
+
+impl ChunkDelta {
+    pub fn is_empty(&self) -> bool {
+        self.density.is_empty() && self.paint.is_empty()
+    }
+}
+
+/// Правки рельефа: разреженный слой дельт по чанкам поверх процедурной плотности.
+///
+/// Точки сетки правок стоят в целых метрах мира. Неправленые чанки остаются
+/// чисто процедурными, а сохраняются только дельты.
+#[derive(Resource, Default)]
+pub struct VoxelEdits {
+    deltas: HashMap<[i128; 3], ChunkDelta>,
+    // чанки, меш которых устарел после правок
+    dirty: HashSet<[i128; 3]>,
+    pub(crate) history: EditHistory,
+    // число проходов покраски; соль дизеринга, чтобы повторные слабые мазки
+    // докрашивали новые точки, а не те же самые
+    pub(crate) paint_passes: u64,
+}
+
+// сохранение правок: только непустые дельты чанков
+#[derive(Serialize, Deserialize)]
+struct SavedEdits {
+    chunks: Vec<([i64; 3], ChunkDelta)>,
+}
+
+impl VoxelEdits {
+    // число точек сетки правок на сторону чанка: шаг в один метр
+    pub fn resolution() -> usize {
+        VoxelChunk::size_chunk() as usize + 1
+    }
+
+    pub fn delta(&self, coordinates: [i128; 3]) -> Option<&ChunkDelta> {
+        self.deltas.get(&coordinates)
+    }
+
+    // в чанке есть свои правки плотности или покраски
+    pub fn is_edited(&self, coordinates: [i128; 3]) -> bool {
+        self.deltas.contains_key(&coordinates)
+    }
+
+    pub fn edited_chunks(&self) -> impl Iterator<Item = &[i128; 3]> {
+        self.deltas.keys()
+    }
+
+    // правки меняют сетку чанка: его собственные или соседей по верхней границе
+    pub fn affects(&self, coordinates: [i128; 3]) -> bool {
+        (0..8).any(|corner| {
+            self.deltas.contains_key(&[
+                coordinates[0] + (corner & 1),
+                coordinates[1] + ((corner >> 1) & 1),
+                coordinates[2] + ((corner >> 2) & 1),
+            ])
+        })
+    }
+
+    pub fn is_dirty(&self, coordinates: [i128; 3]) -> bool {
+        self.dirty.contains(&coordinates)
+    }
+
+    pub fn dirty_chunks(&self) -> impl Iterator<Item = &[i128; 3]> {
+        self.dirty.iter()
+    }
+
+    pub fn mark_clean(&mut self, coordinates: [i128; 3]) {
+        self.dirty.remove(&coordinates);
+    }
+
+    // записывает правки плотности в сетку чанка любого LOD. В полной сетке
+    // точки правок совпадают с узлами. В прореженной узел получает самое сильное
+    // изменение среди точек своей ячейки [узел, узел + шаг), поэтому правки
+    // меньше шага не пропадают на дальних чанках. Ячейки привязаны к сетке мира,
+    // так что общие узлы соседних чанков получают одинаковые значения
+    pub fn apply(
+        &self,
+        density: &dyn DensityFunction,
+        chunk: &VoxelChunk,
+        voxel_grid: &mut VoxelGrid,
+    ) {
+        let size = VoxelChunk::size_chunk() as usize;
+        let cells = voxel_grid.resolution() - 1;
+        if cells == 0 || !size.is_multiple_of(cells) {
+            return;
+        }
+        let step = size / cells;
+        let coordinates = chunk.coordinates();
+        let origin = chunk.world_origin();
+        let mut strongest: HashMap<[usize; 3], f64> = HashMap::new();
+        for corner in 0..8 {
+            let offset = [corner & 1, (corner >> 1) & 1, (corner >> 2) & 1];
+            let owner = [0, 1, 2].map(|axis| coordinates[axis] + offset[axis] as i128);
+            let Some(delta) = self.deltas.get(&owner) else {
+                continue;
+            };
+            for (local, value) in &delta.density {
+                let index = [0, 1, 2].map(|axis| offset[axis] * size + local[axis] as usize);
+                if step == 1 {
+                    if index.iter().all(|value| *value <= size) {
+                        voxel_grid.write(index[0], index[1], index[2], *value);
+                    }
+                    continue;
+                }
+                let node = index.map(|value| value / step);
+                if node.iter().any(|value| *value > cells) {
+                    continue;
+                }
+                let point = [0, 1, 2].map(|axis| (origin[axis] + index[axis] as i64) as f64);
+                let change = *value as f64 - density.sample(point);
+                strongest
+                    .entry(node)
+                    .and_modify(|best| {
+                        if change.abs() > best.abs() {
+                            *best = change;
+                        }
+                    })
+                    .or_insert(change);
+            }
+        }
+        for (node, change) in strongest {
+            let value = voxel_grid.read(node[0], node[1], node[2]) as f64 + change;
+            voxel_grid.write(node[0], node[1], node[2], value as f32);
+        }
+    }
+
+    // полная сетка чанка с правками, None если правки его не касаются
+    pub fn grid(&self, density: &dyn DensityFunction, coordinates: [i128; 3]) -> Option<VoxelGrid> {
+        if !self.affects(coordinates) {
+            return None;
+        }
+        let chunk = VoxelChunk::new(coordinates);
+        let mut voxel_grid = chunk.sample_voxel_grid_parallel(
+            density,
+            ResolutionOfTheGrid::new(VoxelEdits::resolution() as u64),
+        );
+        self.apply(density, &chunk, &mut voxel_grid);
+        Some(voxel_grid)
+    }
+
+    // применяет кисть с силой strength (0..1) и возвращает измененные чанки
+    pub fn apply_brush(
+        &mut self,
+        density: &dyn DensityFunction,
+        brush: &Brush,
+        operation: BrushOperation,
+        strength: f64,
+    ) -> Vec<[i128; 3]> {
+        let strength = strength.clamp(0., 1.);
+        self.record(|edits| {
+            edits.edit_points(density, brush.min, brush.max, |_, point, old| {
+                let distance = brush.shape.distance(point.map(|value| value as f64));
+                // дальше узкой полосы у фигуры кисть не двигает поверхность
+                if distance >= BRUSH_BAND {
+                    return None;
+                }
+                let old = old as f64;
+                let target = match operation {
+                    BrushOperation::Add => old.max(-distance),
+                    BrushOperation::Remove => old.min(distance),
+                };
+                Some((old + (target - old) * strength) as f32)
+            })
+        })
+    }
+
+    // значение плотности, заменяющее процедурное в точке сетки правок
+    pub fn override_at(&self, point: [i64; 3]) -> Option<f32> {
+        let (owner, local) = split_point(point);
+        self.deltas
+            .get(&owner)
+            .and_then(|delta| delta.density.get(&local).copied())
+    }
+
+    // плотность в точке сетки правок: из дельты или процедурная
+    pub fn value_at(&self, density: &dyn DensityFunction, point: [i64; 3]) -> f32 {
+        self.override_at(point)
+            .unwrap_or_else(|| density.sample(point.map(|value| value as f64)) as f32)
+    }
+
+    // плотность в любой точке: в ячейке с правками - трилинейно по ее углам
+    pub fn sample(&self, density: &dyn DensityFunction, position: [f64; 3]) -> f64 {
+        let cell = position.map(|value| value.floor() as i64);
+        let corners: [[i64; 3]; 8] = std::array::from_fn(|corner| {
+            [
+                cell[0] + (corner & 1) as i64,
+                cell[1] + ((corner >> 1) & 1) as i64,
+                cell[2] + ((corner >> 2) & 1) as i64,
+            ]
+        });
+        if corners
+            .iter()
+            .all(|point| self.override_at(*point).is_none())
+        {
+            return density.sample(position);
+        }
+        let t = [0, 1, 2].map(|axis| position[axis] - cell[axis] as f64);
+        let mut value = 0.;
+        for (corner, point) in corners.iter().enumerate() {
+            let offset = [corner & 1, (corner >> 1) & 1, (corner >> 2) & 1];
+            let weight = (0..3)
+                .map(|axis| {
+                    if offset[axis] == 1 {
+                        t[axis]
+                    } else {
+                        1. - t[axis]
+                    }
+                })
+                .product::<f64>();
+            value += weight * self.value_at(density, *point) as f64;
+        }
+        value
+    }
+
+    // материал с учетом покраски ближайшей точки сетки правок
+    pub fn material(&self, density: &dyn DensityFunction, position: [f64; 3]) -> VoxelMaterial {
+        match self.painted_material(position.map(|value| value.round() as i64)) {
+            Some(material) => material,
+            None => density.material(position),
+        }
+    }
+
+    pub fn painted_material(&self, point: [i64; 3]) -> Option<VoxelMaterial> {
+        let (owner, local) = split_point(point);
+        self.deltas
+            .get(&owner)
+            .and_then(|delta| delta.paint.get(&local).copied())
+    }
+
+    // плотность мира вместе с правками, например для цвета вершин и лучей
+    pub fn over<'a>(&'a self, density: &'a dyn DensityFunction) -> EditedDensity<'a> {
+        EditedDensity {
+            density,
+            edits: self,
+        }
+    }
+
+    // правки в RON: только дельты, процедурная часть восстанавливается из сида
+    pub fn to_ron(&self) -> Result<String, ron::Error> {
+        let mut chunks: Vec<([i64; 3], ChunkDelta)> = self
+            .deltas
+            .iter()
+            .map(|(coordinates, delta)| (coordinates.map(|value| value as i64), delta.clone()))
+            .collect();
+        chunks.sort_by_key(|(coordinates, _)| *coordinates);
+        ron::ser::to_string_pretty(&SavedEdits { chunks }, ron::ser::PrettyConfig::default())
+    }
+
+    // загруженные правки помечают свои чанки для перестройки
+    pub fn from_ron(source: &str) -> Result<Self, ron::error::SpannedError> {
+        let saved: SavedEdits = ron::from_str(source)?;
+        let mut edits = VoxelEdits::default();
+        for (coordinates, delta) in saved.chunks {
+            if delta.is_empty() {
+                continue;
+            }
+            let coordinates = coordinates.map(|value| value as i128);
+            edits.mark_dirty_around(coordinates);
+            edits.deltas.insert(coordinates, delta);
+        }
+        Ok(edits)
+    }
+
+    // меняет точки куба [min, max]: compute получает точку и ее старое значение
+    // и возвращает новое. Сначала все новые значения считаются по старому
+    // состоянию, затем записываются, поэтому результат не зависит от порядка
+    // обхода, даже если compute читает соседние точки
+    pub(crate) fn edit_points(
+        &mut self,
+        density: &dyn DensityFunction,
+        min: [f64; 3],
+        max: [f64; 3],
+        mut compute: impl FnMut(&VoxelEdits, [i64; 3], f32) -> Option<f32>,
+    ) -> Vec<[i128; 3]> {
+        let low = min.map(|value| value.ceil() as i64);
+        let high = max.map(|value| value.floor() as i64);
+        let mut changes = Vec::new();
+        for z in low[2]..=high[2] {
+            for y in low[1]..=high[1] {
+                for x in low[0]..=high[0] {
+                    let point = [x, y, z];
+                    let old = self.value_at(density, point);
+                    match compute(self, point, old) {
+                        Some(new) if new != old => changes.push((point, new)),
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        let mut touched = HashSet::new();
+        for (point, new) in changes {
+            // значение, совпавшее с процедурным, из дельты убирается
+            let procedural = density.sample(point.map(|value| value as f64)) as f32;
+            self.set_override(point, (new != procedural).then_some(new));
+            touched.extend(chunks_affected(point));
+        }
+        self.finish_edit(touched)
+    }
+
+    // записывает или убирает значение плотности точки, не помечая чанки;
+    // в открытом штрихе прежнее значение попадает в историю
+    pub(crate) fn set_override(&mut self, point: [i64; 3], value: Option<f32>) {
+        let before = self.override_at(point);
+        if let Some(stroke) = &mut self.history.stroke {
+            stroke.record_density(point, before, value);
+        }
+        let (owner, local) = split_point(point);
+        match value {
+            Some(value) => {
+                self.deltas
+                    .entry(owner)
+                    .or_default()
+                    .density
+                    .insert(local, value);
+            }
+            None => {
+                if let Some(delta) = self.deltas.get_mut(&owner) {
+                    delta.density.remove(&local);
+                    if delta.is_empty() {
+                        self.deltas.remove(&owner);
+                    }
+                }
+            }
+        }
+    }
+
+    // записывает или убирает покраску точки, не помечая чанки
+    pub(crate) fn set_paint(&mut self, point: [i64; 3], material: Option<VoxelMaterial>) {
+        let before = self.painted_material(point);
+        if let Some(stroke) = &mut self.history.stroke {
+            stroke.record_paint(point, before, material);
+        }
+        let (owner, local) = split_point(point);
+        match material {
+            Some(material) => {
+                self.deltas
+                    .entry(owner)
+                    .or_default()
+                    .paint
+                    .insert(local, material);
+            }
+            None => {
+                if let Some(delta) = self.deltas.get_mut(&owner) {
+                    delta.paint.remove(&local);
+                    if delta.is_empty() {
+                        self.deltas.remove(&owner);
+                    }
+                }
+            }
+        }
+    }
+
+    // красит точку сетки правок; возвращает чанки, цвет которых устарел
+    pub(crate) fn paint_point(
+        &mut self,
+        point: [i64; 3],
+        material: VoxelMaterial,
+    ) -> Vec<[i128; 3]> {
+        if self.painted_material(point) == Some(material) {
+            return Vec::new();
+        }
+        self.set_paint(point, Some(material));
+        self.finish_edit(chunks_containing(point))
+    }
+
+    // помечает чанки для перестройки и возвращает их по порядку
+    pub(crate) fn finish_edit(
+        &mut self,
+        touched: impl IntoIterator<Item = [i128; 3]>,
+    ) -> Vec<[i128; 3]> {
+        let mut touched: Vec<[i128; 3]> = touched.into_iter().collect();
+        touched.sort_unstable();
+        touched.dedup();
+        self.dirty.extend(touched.iter().copied());
+        touched
+    }
+
+    // сетки чанка и соседей снизу содержат точки его дельты
+    fn mark_dirty_around(&mut self, coordinates: [i128; 3]) {
+        for corner in 0..8 {
+            self.dirty.insert([
+                coordinates[0] - (corner & 1),
+                coordinates[1] - ((corner >> 1) & 1),
+                coordinates[2] - ((corner >> 2) & 1),
+            ]);
+        }
+    }
+}
+
+/// Плотность мира с учетом правок и покраски. Получается через `VoxelEdits::over`.
+pub struct EditedDensity<'a> {
+    density: &'a dyn DensityFunction,
+    edits: &'a VoxelEdits,
+}
+
+impl DensityFunction for EditedDensity<'_> {
+    fn sample(&self, position: [f64; 3]) -> f64 {
+        self.edits.sample(self.density, position)
+    }
+
+    fn material(&self, position: [f64; 3]) -> VoxelMaterial {
+        self.edits.material(self.density, position)
+    }
+
+    fn surface_height(&self, position: [f64; 2]) -> Option<f64> {
+        self.density.surface_height(position)
+    }
+
+    fn water_level(&self, position: [f64; 2]) -> Option<f64> {
+        self.density.water_level(position)
+    }
+
+    fn depth(&self, position: [f64; 3]) -> Option<f64> {
+        self.density.depth(position)
+    }
+
+    fn up(&self, position: [f64; 3]) -> [f64; 3] {
+        self.density.up(position)
+    }
+}
+
+// чанк, которому принадлежит точка сетки правок, и ее индекс в дельте
+fn split_point(point: [i64; 3]) -> ([i128; 3], [u8; 3]) {
+    let size = VoxelChunk::size_chunk() as i64;
+    (
+        point.map(|value| value.div_euclid(size) as i128),
+        point.map(|value| value.rem_euclid(size) as u8),
+    )
+}
+
+// чанки, сетка которых хоть в одном LOD меняется от правки плотности точки:
+// кроме содержащих ее, это чанки, у которых точка попадает в ячейку верхнего узла
+pub(crate) fn chunks_affected(point: [i64; 3]) -> Vec<[i128; 3]> {
+    let (owner, local) = split_point(point);
+    let range = |axis: usize| {
+        let low = owner[axis] - ((local[axis] as usize) < COARSEST_STEP) as i128;
+        low..=owner[axis]
+    };
+    let mut chunks = Vec::new();
+    for z in range(2) {
+        for y in range(1) {
+            for x in range(0) {
+                chunks.push([x, y, z]);
+            }
+        }
+    }
+    chunks
+}
+
+// чанки, в сетках которых есть точка; точка на границе есть в обоих соседних
+pub(crate) fn chunks_containing(point: [i64; 3]) -> Vec<[i128; 3]> {
+    let (owner, local) = split_point(point);
+    let range = |axis: usize| {
+        let low = owner[axis] - (local[axis] == 0) as i128;
+        low..=owner[axis]
+    };
+    let mut chunks = Vec::new();
+    for z in range(2) {
+        for y in range(1) {
+            for x in range(0) {
+                chunks.push([x, y, z]);
+            }
+        }
+    }
+    chunks
+}
+
+/// Доступ к редактированию мира из систем: `fn dig(mut world: VoxelWorld) { ... }`.
+///
+/// Измененные чанки помечаются и перестраиваются `WorldPlugin` в порядке
+/// удаленности от камеры, по несколько за кадр. Кэш плотности хранит только
+/// процедурные сетки, поэтому правки его не сбрасывают.
+#[derive(SystemParam)]
+pub struct VoxelWorld<'w> {
+    density: Res<'w, WorldDensity>,
+    edits: ResMut<'w, VoxelEdits>,
+}
+
+impl VoxelWorld<'_> {
+    pub fn apply_brush(
+        &mut self,
+        brush: &Brush,
+        operation: BrushOperation,
+        strength: f64,
+    ) -> Vec<[i128; 3]> {
+        self.edits
+            .apply_brush(self.density.function(), brush, operation, strength)
+    }
+
+    pub fn sculpt(&mut self, brush: &SculptBrush, tool: &SculptTool) -> Vec<[i128; 3]> {
+        self.edits.sculpt(self.density.function(), brush, tool)
+    }
+
+    // правки между началом и концом штриха отменяются одним шагом
+    pub fn begin_stroke(&mut self) {
+        self.edits.begin_stroke();
+    }
+
+    pub fn end_stroke(&mut self) {
+        self.edits.end_stroke();
+    }
+
+    pub fn undo(&mut self) -> Vec<[i128; 3]> {
+        self.edits.undo()
+    }
+
+    pub fn redo(&mut self) -> Vec<[i128; 3]> {
+        self.edits.redo()
+    }
+
+    pub fn raycast(
+        &self,
+        origin: [f64; 3],
+        direction: [f64; 3],
+        max_distance: f64,
+    ) -> Option<RayHit> {
+        self.edits
+            .raycast(self.density.function(), origin, direction, max_distance)
+    }
+
+    pub fn density(&self) -> &WorldDensity {
+        &self.density
+    }
+
+    pub fn edits(&self) -> &VoxelEdits {
+        &self.edits
+    }
+}
@@ -0,0 +1,314 @@
+use std::sync::Arc;
+
+use super::density::DensityFunction;
+use super::material::VoxelMaterial;
+use super::random::{self, Random};
+use super::tiles::{TileCache, TileLayout};
+use super::world_config::WorldSeed;
+
+/// Настройки эрозии рельефа.
+///
+/// Эрозия считается по тайлам карты высот, которые больше чанка. Каждый тайл
+/// моделируется на области вдвое шире себя, а результаты соседних тайлов
+/// смешиваются билинейно, поэтому на границах тайлов нет ступенек.
+#[derive(Clone, Debug)]
+pub struct ErosionSettings {
+    // число ячеек карты высот на сторону тайла
+    pub tile_cells: usize,
+    // размер ячейки карты высот в метрах
+    pub cell_size: f64,
+    // сколько тайлов хранится в кэше
+    pub cache_capacity: usize,
+    // капель на одну ячейку карты высот
+    pub droplets_per_cell: f64,
+    pub droplet_lifetime: usize,
+    // насколько капля сохраняет направление (0..1)
+    pub inertia: f64,
+    pub sediment_capacity: f64,
+    pub min_sediment_capacity: f64,
+    pub erode_speed: f64,
+    pub deposit_speed: f64,
+    pub evaporate_speed: f64,
+    pub gravity: f64,
+    // проходов осыпания склонов
+    pub thermal_iterations: usize,
+    // наибольший устойчивый перепад высот между соседними ячейками в метрах
+    pub talus: f64,
+    // доля избытка, которая осыпается за один проход
+    pub thermal_rate: f64,
+}
+
+impl Default for ErosionSettings {
+    fn default() -> Self {
+        ErosionSettings {
+            tile_cells: 64,
+            cell_size: 2.,
+            cache_capacity: 64,
+            droplets_per_cell: 0.6,
+            droplet_lifetime: 40,
+            inertia: 0.1,
+            sediment_capacity: 4.,
+            min_sediment_capacity: 0.01,
+            erode_speed: 0.3,
+            deposit_speed: 0.3,
+            evaporate_speed: 0.02,
+            gravity: 4.,
+            thermal_iterations: 8,
+            talus: 1.2,
+            thermal_rate: 0.5,
+        }
+    }
+}
+
+// квадратная карта высот со стороной side точек
+struct Heightmap {
+    side: usize,
+    heights: Vec<f64>,
+}
+
+impl Heightmap {
+    fn index(&self, x: usize, z: usize) -> usize {
+        x + z * self.side
+    }
+
+    // высота и градиент в дробной точке карты
+    fn height_and_gradient(&self, x: f64, z: f64) -> (f64, [f64; 2]) {
+        let (cell_x, cell_z) = (x.floor() as usize, z.floor() as usize);
+        let (u, v) = (x - cell_x as f64, z - cell_z as f64);
+        let h00 = self.heights[self.index(cell_x, cell_z)];
+        let h10 = self.heights[self.index(cell_x + 1, cell_z)];
+        let h01 = self.heights[self.index(cell_x, cell_z + 1)];
+        let h11 = self.heights[self.index(cell_x + 1, cell_z + 1)];
+        let gradient = [
+            (h10 - h00) * (1. - v) + (h11 - h01) * v,
+            (h01 - h00) * (1. - u) + (h11 - h10) * u,
+        ];
+        let height =
+            h00 * (1. - u) * (1. - v) + h10 * u * (1. - v) + h01 * (1. - u) * v + h11 * u * v;
+        (height, gradient)
+    }
+
+    // добавляет amount в четыре соседние точки пропорционально близости
+    fn add_bilinear(&mut self, x: f64, z: f64, amount: f64) {
+        let (cell_x, cell_z) = (x.floor() as usize, z.floor() as usize);
+        let (u, v) = (x - cell_x as f64, z - cell_z as f64);
+        let i00 = self.index(cell_x, cell_z);
+        let i10 = self.index(cell_x + 1, cell_z);
+        let i01 = self.index(cell_x, cell_z + 1);
+        let i11 = self.index(cell_x + 1, cell_z + 1);
+        self.heights[i00] += amount * (1. - u) * (1. - v);
+        self.heights[i10] += amount * u * (1. - v);
+        self.heights[i01] += amount * (1. - u) * v;
+        self.heights[i11] += amount * u * v;
+    }
+
+    fn hydraulic_erosion(&mut self, settings: &ErosionSettings, random: &mut Random) {
+        let limit = (self.side - 2) as f64;
+        let droplets = (settings.droplets_per_cell * (self.side * self.side) as f64) as usize;
+        for _ in 0..droplets {
+            let mut position = [random.range(0., limit), random.range(0., limit)];
+            let mut direction = [0., 0.];
+            let mut speed = 1.;
+            let mut water = 1.;
+            let mut sediment = 0.;
+
+            for _ in 0..settings.droplet_lifetime {
+                let (height, gradient) = self.height_and_gradient(position[0], position[1]);
+                direction = [
+                    direction[0] * settings.inertia - gradient[0] * (1. - settings.inertia),
+                    direction[1] * settings.inertia - gradient[1] * (1. - settings.inertia),
+                ];
+                let length = (direction[0] * direction[0] + direction[1] * direction[1]).sqrt();
+                if length < 1e-9 {
+                    break;
+                }
+                direction = [direction[0] / length, direction[1] / length];
+                let old_position = position;
+                position = [position[0] + direction[0], position[1] + direction[1]];
+                if !(0. ..limit).contains(&position[0]) || !(0. ..limit).contains(&position[1]) {
+                    break;
+                }
+
+                let delta_height = self.height_and_gradient(position[0], position[1]).0 - height;
+                let capacity = (-delta_height * speed * water * settings.sediment_capacity)
+                    .max(settings.min_sediment_capacity);
+
+                if sediment > capacity || delta_height > 0. {
+                    // капля поднимается или перегружена - откладывает осадок
+                    let amount = if delta_height > 0. {
+                        delta_height.min(sediment)
+                    } else {
+                        (sediment - capacity) * settings.deposit_speed
+                    };
+                    sediment -= amount;
+                    self.add_bilinear(old_position[0], old_position[1], amount);
+                } else {
+                    // капля размывает склон, но не глубже перепада высот
+                    let amount = ((capacity - sediment) * settings.erode_speed).min(-delta_height);
+                    sediment += amount;
+                    self.add_bilinear(old_position[0], old_position[1], -amount);
+                }
+
+                speed = (speed * speed - delta_height * settings.gravity)
+                    .max(0.)
+                    .sqrt();
+                water *= 1. - settings.evaporate_speed;
+            }
+        }
+    }
+
+    fn thermal_erosion(&mut self, settings: &ErosionSettings) {
+        let mut changes = vec![0.; self.heights.len()];
+        for _ in 0..settings.thermal_iterations {
+            changes.iter_mut().for_each(|change| *change = 0.);
+            for z in 0..self.side {
+                for x in 0..self.side {
+                    let index = self.index(x, z);
+                    let height = self.heights[index];
+                    // сравниваем только с правым и нижним соседом, чтобы каждая пара учитывалась один раз
+                    for (neighbour_x, neighbour_z) in [(x + 1, z), (x, z + 1)] {
+                        if neighbour_x >= self.side || neighbour_z >= self.side {
+                            continue;
+                        }
+                        let neighbour = self.index(neighbour_x, neighbour_z);
+                        let difference = height - self.heights[neighbour];
+                        if difference.abs() <= settings.talus {
+                            continue;
+                        }
+                        let moved = (difference.abs() - settings.talus)
+                            * settings.thermal_rate
+                            * 0.5
+                            * difference.signum();
+                        changes[index] -= moved;
+                        changes[neighbour] += moved;
+                    }
+                }
+            }
+            self.heights
+                .iter_mut()
+                .zip(&changes)
+                .for_each(|(height, change)| *height += change);
+        }
+    }
+}
+
+// изменения высот одного тайла после эрозии, точки с шагом cell_size
+struct ErodedTile {
+    deltas: Vec<f32>,
+}
+
+/// Рельеф, высота поверхности которого прошла гидравлическую и термическую эрозию.
+///
+/// Работает только с рельефом, у которого есть `surface_height` и плотность у
+/// поверхности равна `surface_height - y` в метрах: к плотности добавляется
+/// разница между размытой и исходной высотой. Там, где высоты нет, рельеф не меняется.
+pub struct ErodedTerrain {
+    terrain: Arc<dyn DensityFunction>,
+    seed: WorldSeed,
+    settings: ErosionSettings,
+    layout: TileLayout,
+    cache: TileCache<ErodedTile>,
+}
+
+impl ErodedTerrain {
+    pub fn new(
+        terrain: Arc<dyn DensityFunction>,
+        seed: WorldSeed,
+        settings: ErosionSettings,
+    ) -> Self {
+        ErodedTerrain {
+            terrain,
+            seed: seed.sub_seed("erosion"),
+            layout: TileLayout {
+                tile_cells: settings.tile_cells,
+                cell_size: settings.cell_size,
+            },
+            cache: TileCache::new(settings.cache_capacity),
+            settings,
+        }
+    }
+
+    pub fn settings(&self) -> &ErosionSettings {
+        &self.settings
+    }
+
+    fn erode_tile(&self, tile: [i64; 2]) -> ErodedTile {
+        let side = self.layout.side();
+        let mut heightmap = Heightmap {
+            side,
+            heights: Vec::with_capacity(side * side),
+        };
+        // точки без поверхности не размываются
+        let mut has_surface = Vec::with_capacity(side * side);
+        for z in 0..side {
+            for x in 0..side {
+                let height = self.terrain.surface_height(self.layout.point(tile, x, z));
+                has_surface.push(height.is_some());
+                heightmap.heights.push(height.unwrap_or(0.));
+            }
+        }
+        let original = heightmap.heights.clone();
+
+        let mut random = Random::new(random::hash_coordinates(self.seed.value(), &tile));
+        heightmap.hydraulic_erosion(&self.settings, &mut random);
+        heightmap.thermal_erosion(&self.settings);
+
+        ErodedTile {
+            deltas: heightmap
+                .heights
+                .iter()
+                .zip(original)
+                .zip(has_surface)
+                .map(|((eroded, original), has_surface)| {
+                    if has_surface {
+                        (eroded - original) as f32
+                    } else {
+                        0.
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    pub fn erosion_delta(&self, position: [f64; 2]) -> f64 {
+        self.layout.blend(position, |tile| {
+            let eroded_tile = self
+                .cache
+                .get_or_insert_with(tile, || self.erode_tile(tile));
+            self.layout
+                .sample_layer(tile, &eroded_tile.deltas, position)
+        })
+    }
+}
+
+impl DensityFunction for ErodedTerrain {
+    fn sample(&self, position: [f64; 3]) -> f64 {
+        self.terrain.sample(position) + self.erosion_delta([position[0], position[2]])
+    }
+
+    fn sample_column(&self, column: [f64; 2], heights: &[f64], densities: &mut [f64]) {
+        self.terrain.sample_column(column, heights, densities);
+        let delta = self.erosion_delta(column);
+        for density in densities.iter_mut() {
+            *density += delta;
+        }
+    }
+
+    fn material(&self, position: [f64; 3]) -> VoxelMaterial {
+        self.terrain.material(position)
+    }
+
+    fn surface_height(&self, position: [f64; 2]) -> Option<f64> {
+        self.terrain
+            .surface_height(position)
+            .map(|height| height + self.erosion_delta(position))
+    }
+
+    fn water_level(&self, position: [f64; 2]) -> Option<f64> {
+        self.terrain.water_level(position)
+    }
+
+    fn up(&self, position: [f64; 3]) -> [f64; 3] {
+        self.terrain.up(position)
+    }
+}
@@ -0,0 +1,116 @@
+use bevy::prelude::*;
+
+use super::chunk_from_marching_cubes::VoxelChunk;
+
+// через сколько чанков от нуля сцены камера вызывает сдвиг мира
+const SHIFT_THRESHOLD_IN_CHUNKS: u16 = 16;
+
+/// Плавающее начало координат.
+///
+/// `Transform` всех сущностей хранится относительно `origin`, поэтому камера
+/// всегда остается рядом с нулем и f32 не теряет точность вдали от центра мира.
+/// Генерация и поиск чанков работают с истинными координатами мира.
+#[derive(Resource)]
+pub struct FloatingOrigin {
+    // истинные мировые координаты нуля сцены в метрах (всегда кратны размеру чанка)
+    origin: [i64; 3],
+    // расстояние от нуля сцены в метрах, после которого выполняется сдвиг
+    threshold: f32,
+}
+
+impl FloatingOrigin {
+    pub fn new(threshold: f32) -> Self {
+        FloatingOrigin {
+            origin: [0, 0, 0],
+            threshold,
+        }
+    }
+
+    pub fn origin(&self) -> [i64; 3] {
+        self.origin
+    }
+
+    pub fn threshold(&self) -> f32 {
+        self.threshold
+    }
+
+    // координаты сцены -> истинные координаты мира
+    pub fn scene_to_world(&self, translation: Vec3) -> [f64; 3] {
+        [
+            self.origin[0] as f64 + translation.x as f64,
+            self.origin[1] as f64 + translation.y as f64,
+            self.origin[2] as f64 + translation.z as f64,
+        ]
+    }
+
+    // истинные координаты мира -> координаты сцены
+    pub fn world_to_scene(&self, position: [f64; 3]) -> Vec3 {
+        Vec3::new(
+            (position[0] - self.origin[0] as f64) as f32,
+            (position[1] - self.origin[1] as f64) as f32,
+            (position[2] - self.origin[2] as f64) as f32,
+        )
+    }
+
+    // точка сцены, в которой должен стоять угол чанка
+    pub fn chunk_translation(&self, chunk: &VoxelChunk) -> Vec3 {
+        let world_origin = chunk.world_origin();
+        Vec3::new(
+            (world_origin[0] - self.origin[0]) as f32,
+            (world_origin[1] - self.origin[1]) as f32,
+            (world_origin[2] - self.origin[2]) as f32,
+        )
+    }
+
+    // сдвиг, который вернет точку сцены к нулю, с шагом в целый чанк
+    pub fn shift_for(&self, translation: Vec3) -> Option<[i64; 3]> {
+        if translation.abs().max_element() < self.threshold {
+            return None;
+        }
+        let size_chunk = VoxelChunk::size_chunk() as f32;
+        Some([
+            ((translation.x / size_chunk).floor() * size_chunk) as i64,
+            ((translation.y / size_chunk).floor() * size_chunk) as i64,
+            ((translation.z / size_chunk).floor() * size_chunk) as i64,
+        ])
+    }
+
+    // переносит ноль сцены на shift метров мира
+    pub fn shift(&mut self, shift: [i64; 3]) {
+        for (origin, delta) in self.origin.iter_mut().zip(shift) {
+            *origin += delta;
+        }
+    }
+}
+
+impl Default for FloatingOrigin {
+    fn default() -> Self {
+        FloatingOrigin::new(SHIFT_THRESHOLD_IN_CHUNKS as f32 * VoxelChunk::size_chunk() as f32)
+    }
+}
+
+// выполняется до распространения трансформаций, чтобы GlobalTransform
+// в следующем Update уже соответствовал новому началу координат
+#[allow(clippy::type_complexity)]
+pub fn shift_floating_origin(
+    mut floating_origin: ResMut<FloatingOrigin>,
+    mut transform_queries: ParamSet<(
+        Query<&Transform, With<Camera3d>>,
+        // корневые узлы интерфейса (прицел) стоят в координатах экрана
+        Query<&mut Transform, (Without<Parent>, Without<Node>)>,
+    )>,
+) {
+    let Ok(camera_transform) = transform_queries.p0().get_single().copied() else {
+        return;
+    };
+    let Some(shift) = floating_origin.shift_for(camera_transform.translation) else {
+        return;
+    };
+
+    let shift_in_scene = Vec3::new(shift[0] as f32, shift[1] as f32, shift[2] as f32);
+    // дочерние сущности двигаются вместе с родителем
+    for mut transform in transform_queries.p1().iter_mut() {
+        transform.translation -= shift_in_scene;
+    }
+    floating_origin.shift(shift);
+}
@@ -0,0 +1,365 @@
+use std::sync::Arc;
+
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureFormat},
+};
+
+use super::biome::BiomeTerrain;
+use super::caves::smoothstep;
+use super::density::{DensityFunction, WorldDensity};
+use super::material::VoxelMaterial;
+use super::world_config::WorldConfig;
+
+/// Способ интерполяции между пикселями карты высот.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HeightmapSampling {
+    #[default]
+    Bilinear,
+    // кубический сплайн Катмулла-Рома по 4x4 пикселям, без изломов на границах пикселей
+    Bicubic,
+}
+
+/// Размещение карты высот в мире. Расстояния в метрах.
+#[derive(Clone, Debug)]
+pub struct HeightmapSettings {
+    // мировые координаты (x, z) угла изображения с первым пикселем
+    pub origin: [f64; 2],
+    // размер изображения в мире по x и z
+    pub extents: [f64; 2],
+    // высота черного пикселя
+    pub base_height: f64,
+    // разница высот белого и черного пикселя
+    pub vertical_scale: f64,
+    pub sampling: HeightmapSampling,
+    // ширина полосы за краем изображения, на которой оно переходит в процедурный рельеф
+    pub border_blend: f64,
+}
+
+impl Default for HeightmapSettings {
+    fn default() -> Self {
+        HeightmapSettings {
+            origin: [-512., -512.],
+            extents: [1024., 1024.],
+            base_height: -32.,
+            vertical_scale: 128.,
+            sampling: HeightmapSampling::default(),
+            border_blend: 64.,
+        }
+    }
+}
+
+/// Карта высот: значения пикселей, приведенные к диапазону 0..1.
+#[derive(Clone, Debug)]
+pub struct Heightmap {
+    width: usize,
+    height: usize,
+    data: Vec<f32>,
+}
+
+impl Heightmap {
+    // значения идут по строкам, первая строка соответствует наименьшему z
+    pub fn new(width: usize, height: usize, data: Vec<f32>) -> Self {
+        assert!(width > 0 && height > 0, "heightmap should not be empty");
+        assert_eq!(data.len(), width * height, "heightmap size mismatch");
+        Heightmap {
+            width,
+            height,
+            data,
+        }
+    }
+
+    /// Читает одноканальное изображение (16-битные PNG загружаются в `R16Uint`).
+    /// Для цветных изображений берется красный канал. None, если формат не поддерживается.
+    pub fn from_image(image: &Image) -> Option<Self> {
+        let Extent3d { width, height, .. } = image.texture_descriptor.size;
+        let bytes = &image.data;
+        let data: Vec<f32> = match image.texture_descriptor.format {
+            TextureFormat::R16Uint | TextureFormat::R16Unorm => bytes
+                .chunks_exact(2)
+                .map(|pixel| u16::from_le_bytes([pixel[0], pixel[1]]) as f32 / u16::MAX as f32)
+                .collect(),
+            TextureFormat::R8Unorm | TextureFormat::R8Uint => bytes
+                .iter()
+                .map(|pixel| *pixel as f32 / u8::MAX as f32)
+                .collect(),
+            TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => bytes
+                .chunks_exact(4)
+                .map(|pixel| pixel[0] as f32 / u8::MAX as f32)
+                .collect(),
+            TextureFormat::Rgba16Uint | TextureFormat::Rgba16Unorm => bytes
+                .chunks_exact(8)
+                .map(|pixel| u16::from_le_bytes([pixel[0], pixel[1]]) as f32 / u16::MAX as f32)
+                .collect(),
+            _ => return None,
+        };
+        if data.len() != (width * height) as usize {
+            return None;
+        }
+        Some(Heightmap::new(width as usize, height as usize, data))
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // значение пикселя, координаты за краем прижимаются к краю
+    pub fn pixel(&self, x: i64, y: i64) -> f64 {
+        let x = x.clamp(0, self.width as i64 - 1) as usize;
+        let y = y.clamp(0, self.height as i64 - 1) as usize;
+        self.data[x + y * self.width] as f64
+    }
+
+    // значение в дробной точке, координаты в пикселях
+    pub fn sample(&self, x: f64, y: f64, sampling: HeightmapSampling) -> f64 {
+        let (cell_x, cell_y) = (x.floor() as i64, y.floor() as i64);
+        let (u, v) = (x - cell_x as f64, y - cell_y as f64);
+        match sampling {
+            HeightmapSampling::Bilinear => {
+                self.pixel(cell_x, cell_y) * (1. - u) * (1. - v)
+                    + self.pixel(cell_x + 1, cell_y) * u * (1. - v)
+                    + self.pixel(cell_x, cell_y + 1) * (1. - u) * v
+                    + self.pixel(cell_x + 1, cell_y + 1) * u * v
+            }
+            HeightmapSampling::Bicubic => {
+                let row = |y: i64| {
+                    catmull_rom(
+                        [
+                            self.pixel(cell_x - 1, y),
+                            self.pixel(cell_x, y),
+                            self.pixel(cell_x + 1, y),
+                            self.pixel(cell_x + 2, y),
+                        ],
+                        u,
+                    )
+                };
+                catmull_rom(
+                    [
+                        row(cell_y - 1),
+                        row(cell_y),
+                        row(cell_y + 1),
+                        row(cell_y + 2),
+                    ],
+                    v,
+                )
+            }
+        }
+    }
+}
+
+fn catmull_rom(points: [f64; 4], t: f64) -> f64 {
+    let [p0, p1, p2, p3] = points;
+    p1 + 0.5
+        * t
+        * (p2 - p0 + t * (2. * p0 - 5. * p1 + 4. * p2 - p3 + t * (3. * (p1 - p2) + p3 - p0)))
+}
+
+/// Рельеф по карте высот из изображения.
+///
+/// За краями изображения высота плавно переходит в `fallback` (например,
+/// процедурный рельеф мира), а без него продолжает крайние пиксели.
+pub struct HeightmapTerrain {
+    heightmap: Heightmap,
+    settings: HeightmapSettings,
+    fallback: Option<Arc<dyn DensityFunction>>,
+}
+
+impl HeightmapTerrain {
+    pub fn new(
+        heightmap: Heightmap,
+        settings: HeightmapSettings,
+        fallback: Option<Arc<dyn DensityFunction>>,
+    ) -> Self {
+        HeightmapTerrain {
+            heightmap,
+            settings,
+            fallback,
+        }
+    }
+
+    pub fn settings(&self) -> &HeightmapSettings {
+        &self.settings
+    }
+
+    // высота по изображению, за краем - по крайним пикселям
+    pub fn image_height(&self, position: [f64; 2]) -> f64 {
+        let settings = &self.settings;
+        let x = (position[0] - settings.origin[0]) / settings.extents[0]
+            * (self.heightmap.width - 1) as f64;
+        let y = (position[1] - settings.origin[1]) / settings.extents[1]
+            * (self.heightmap.height - 1) as f64;
+        settings.base_height
+            + self.heightmap.sample(x, y, settings.sampling) * settings.vertical_scale
+    }
+
+    // расстояние от точки до прямоугольника изображения, 0 внутри
+    fn distance_outside(&self, position: [f64; 2]) -> f64 {
+        let settings = &self.settings;
+        let outside = |value: f64, start: f64, extent: f64| {
+            (start - value).max(value - start - extent).max(0.)
+        };
+        outside(position[0], settings.origin[0], settings.extents[0]).hypot(outside(
+            position[1],
+            settings.origin[1],
+            settings.extents[1],
+        ))
+    }
+
+    // вес процедурного рельефа: 0 на изображении, 1 за полосой перехода
+    fn fallback_weight(&self, position: [f64; 2]) -> f64 {
+        if self.fallback.is_none() {
+            return 0.;
+        }
+        smoothstep(
+            0.,
+            self.settings.border_blend,
+            self.distance_outside(position),
+        )
+    }
+
+    // высота поверхности с учетом полосы перехода. None, если там влияет
+    // процедурный рельеф без высоты: по смеси плотностей ее не определить
+    pub fn surface_height(&self, position: [f64; 2]) -> Option<f64> {
+        let image_height = self.image_height(position);
+        let weight = self.fallback_weight(position);
+        match &self.fallback {
+            Some(fallback) if weight > 0. => {
+                let fallback_height = fallback.surface_height(position)?;
+                Some(image_height * (1. - weight) + fallback_height * weight)
+            }
+            _ => Some(image_height),
+        }
+    }
+}
+
+impl DensityFunction for HeightmapTerrain {
+    fn sample(&self, position: [f64; 3]) -> f64 {
+        let image_density = self.image_height([position[0], position[2]]) - position[1];
+        match &self.fallback {
+            Some(fallback) => {
+                let weight = self.fallback_weight([position[0], position[2]]);
+                if weight >= 1. {
+                    return fallback.sample(position);
+                }
+                if weight <= 0. {
+                    return image_density;
+                }
+                image_density * (1. - weight) + fallback.sample(position) * weight
+            }
+            None => image_density,
+        }
+    }
+
+    fn sample_column(&self, column: [f64; 2], heights: &[f64], densities: &mut [f64]) {
+        let image_height = self.image_height(column);
+        let weight = match &self.fallback {
+            Some(fallback) => {
+                let weight = self.fallback_weight(column);
+                if weight > 0. {
+                    fallback.sample_column(column, heights, densities);
+                }
+                weight
+            }
+            None => 0.,
+        };
+        if weight >= 1. {
+            return;
+        }
+        for (height, density) in heights.iter().zip(densities.iter_mut()) {
+            let image_density = image_height - height;
+            *density = if weight <= 0. {
+                image_density
+            } else {
+                image_density * (1. - weight) + *density * weight
+            };
+        }
+    }
+
+    fn material(&self, position: [f64; 3]) -> VoxelMaterial {
+        match &self.fallback {
+            Some(fallback) => fallback.material(position),
+            None => VoxelMaterial::default(),
+        }
+    }
+
+    fn surface_height(&self, position: [f64; 2]) -> Option<f64> {
+        HeightmapTerrain::surface_height(self, position)
+    }
+
+    // вода процедурного рельефа остается только там, где изображение уже не влияет
+    fn water_level(&self, position: [f64; 2]) -> Option<f64> {
+        match &self.fallback {
+            Some(fallback) if self.fallback_weight(position) >= 1. => {
+                fallback.water_level(position)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Изображение, которое используется как карта высот мира.
+///
+/// После загрузки (и после каждого изменения файла) заменяет `WorldDensity`:
+/// слои из `WorldConfig` строятся поверх изображения, а при `procedural_fallback`
+/// за его краями продолжаются биомы из `WorldConfig`.
+#[derive(Resource)]
+pub struct WorldHeightmap {
+    pub image: Handle<Image>,
+    pub settings: HeightmapSettings,
+    pub procedural_fallback: bool,
+}
+
+pub fn apply_heightmap(
+    mut image_events: EventReader<AssetEvent<Image>>,
+    world_heightmap: Option<Res<WorldHeightmap>>,
+    images: Res<Assets<Image>>,
+    config: Res<WorldConfig>,
+    mut world_density: ResMut<WorldDensity>,
+) {
+    let Some(world_heightmap) = world_heightmap else {
+        image_events.clear();
+        return;
+    };
+
+    let image_changed = image_events.iter().any(|event| match event {
+        AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+            *handle == world_heightmap.image
+        }
+        AssetEvent::Removed { .. } => false,
+    });
+    if !image_changed && !world_heightmap.is_changed() {
+        return;
+    }
+
+    let Some(image) = images.get(&world_heightmap.image) else {
+        return;
+    };
+    let Some(heightmap) = Heightmap::from_image(image) else {
+        warn!(
+            "unsupported heightmap format {:?}",
+            image.texture_descriptor.format
+        );
+        return;
+    };
+    // слои конвейера (эрозия, реки, пещеры, постройки...) идут поверх
+    // изображения, поэтому постройки встают на его рельеф
+    let fallback = world_heightmap.procedural_fallback.then(|| {
+        Arc::new(BiomeTerrain::new(
+            config.seed,
+            config.terrain_scale,
+            config.biome_size,
+        )) as Arc<dyn DensityFunction>
+    });
+    *world_density = WorldDensity::from_base(
+        &config,
+        Arc::new(HeightmapTerrain::new(
+            heightmap,
+            world_heightmap.settings.clone(),
+            fallback,
+        )),
+    );
+}
@@ -0,0 +1,531 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+use std::rc::Rc;
+use std::sync::Arc;
+
+use super::density::DensityFunction;
+use super::material::VoxelMaterial;
+use super::tiles::TileCache;
+
+// вода тоньше этого слоя не считается водоемом
+const MIN_WATER_DEPTH: f64 = 0.05;
+// наибольшая площадь озера в ячейках; впадина, которая не переливается
+// и при такой площади, становится бессточным озером
+const MAX_LAKE_CELLS: usize = 4096;
+// наибольшая длина спуска к дну впадины в ячейках
+const MAX_DESCENT: usize = 4096;
+// сколько озер подряд может переливаться одно в другое при поиске уровня воды
+const MAX_LAKE_CHAIN: usize = 8;
+// соседи ячейки и расстояния до них в ячейках
+const NEIGHBOURS: [(i64, i64, f64); 8] = [
+    (-1, -1, std::f64::consts::SQRT_2),
+    (0, -1, 1.),
+    (1, -1, std::f64::consts::SQRT_2),
+    (-1, 0, 1.),
+    (1, 0, 1.),
+    (-1, 1, std::f64::consts::SQRT_2),
+    (0, 1, 1.),
+    (1, 1, std::f64::consts::SQRT_2),
+];
+
+/// Настройки моря, рек и озер. Расстояния в метрах.
+///
+/// Стоки считаются на одной сетке ячеек для всего мира: вода течет вниз до
+/// моря или бессточного озера, а тайлы только кэшируют готовые значения.
+#[derive(Clone, Debug)]
+pub struct HydrologySettings {
+    // уровень моря, ниже него вся открытая поверхность под водой
+    pub sea_level: f64,
+    // число ячеек сетки стока на сторону тайла кэша
+    pub tile_cells: usize,
+    // размер ячейки сетки стока в метрах
+    pub cell_size: f64,
+    // сколько тайлов хранится в кэше
+    pub cache_capacity: usize,
+    // площадь водосбора в ячейках, с которой начинается река
+    pub river_threshold: f64,
+    // глубина русла самой полноводной реки
+    pub river_depth: f64,
+    // доля глубины русла, заполненная водой (0..1)
+    pub river_fill: f64,
+    // впадины мельче этого не становятся озерами
+    pub min_lake_depth: f64,
+}
+
+impl Default for HydrologySettings {
+    fn default() -> Self {
+        HydrologySettings {
+            sea_level: -8.,
+            tile_cells: 128,
+            cell_size: 4.,
+            cache_capacity: 32,
+            river_threshold: 400.,
+            river_depth: 4.,
+            river_fill: 0.75,
+            min_lake_depth: 0.5,
+        }
+    }
+}
+
+impl HydrologySettings {
+    // сила реки: русло углубляется с ростом водосбора до полной глубины
+    fn river_strength(&self, accumulation: f64) -> f64 {
+        ((accumulation / self.river_threshold).ln() / 4. + 0.25).min(1.)
+    }
+
+    // водосбор, с которого река достигает полной глубины; больший считать незачем
+    fn saturated_accumulation(&self) -> f64 {
+        self.river_threshold * 3f64.exp()
+    }
+}
+
+// ячейка в очереди заполнения впадины, первой извлекается самая низкая
+#[derive(PartialEq)]
+struct FloodCell {
+    height: f64,
+    cell: [i64; 2],
+}
+
+impl Eq for FloodCell {}
+
+impl Ord for FloodCell {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare_cells((other.height, other.cell), (self.height, self.cell))
+    }
+}
+
+impl PartialOrd for FloodCell {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// полный порядок ячеек: по высоте, при равной высоте по координатам.
+// Вода всегда уходит в ячейку ниже по этому порядку, поэтому стоки не зацикливаются
+fn compare_cells(first: (f64, [i64; 2]), second: (f64, [i64; 2])) -> Ordering {
+    first.0.total_cmp(&second.0).then(first.1.cmp(&second.1))
+}
+
+// впадина, заполненная до перелива
+struct Lake {
+    level: f64,
+    // ячейка за краем, куда переливается вода; None у бессточного озера
+    exit: Option<[i64; 2]>,
+    // ячейки не выше уровня, залитые от дна
+    cells: HashSet<[i64; 2]>,
+}
+
+// расчет стока в окрестности одного тайла. Все значения зависят только от
+// ячейки мира, а не от тайла, поэтому соседние тайлы сходятся без швов
+struct Drainage<'a> {
+    terrain: &'a dyn DensityFunction,
+    settings: &'a HydrologySettings,
+    heights: HashMap<[i64; 2], f64>,
+    receivers: HashMap<[i64; 2], Option<[i64; 2]>>,
+    pits: HashMap<[i64; 2], Option<[i64; 2]>>,
+    lakes: HashMap<[i64; 2], Rc<Lake>>,
+}
+
+impl<'a> Drainage<'a> {
+    fn new(terrain: &'a dyn DensityFunction, settings: &'a HydrologySettings) -> Self {
+        Drainage {
+            terrain,
+            settings,
+            heights: HashMap::new(),
+            receivers: HashMap::new(),
+            pits: HashMap::new(),
+            lakes: HashMap::new(),
+        }
+    }
+
+    // высота ячейки; без поверхности ячейка считается стоком
+    fn height(&mut self, cell: [i64; 2]) -> f64 {
+        if let Some(height) = self.heights.get(&cell) {
+            return *height;
+        }
+        let cell_size = self.settings.cell_size;
+        let height = self
+            .terrain
+            .surface_height([cell[0] as f64 * cell_size, cell[1] as f64 * cell_size])
+            .unwrap_or(f64::NEG_INFINITY);
+        self.heights.insert(cell, height);
+        height
+    }
+
+    fn is_sink(&mut self, cell: [i64; 2]) -> bool {
+        self.height(cell) <= self.settings.sea_level
+    }
+
+    fn is_below(&mut self, first: [i64; 2], second: [i64; 2]) -> bool {
+        let first = (self.height(first), first);
+        let second = (self.height(second), second);
+        compare_cells(first, second) == Ordering::Less
+    }
+
+    // сосед, к которому склон круче всего; None на дне впадины
+    fn descent(&mut self, cell: [i64; 2]) -> Option<[i64; 2]> {
+        let height = self.height(cell);
+        let mut best: Option<([i64; 2], f64)> = None;
+        for (offset_x, offset_z, distance) in NEIGHBOURS {
+            let neighbour = [cell[0] + offset_x, cell[1] + offset_z];
+            if !self.is_below(neighbour, cell) {
+                continue;
+            }
+            let slope = (height - self.height(neighbour)) / distance;
+            if best.is_none_or(|(_, best_slope)| slope > best_slope) {
+                best = Some((neighbour, slope));
+            }
+        }
+        best.map(|(neighbour, _)| neighbour)
+    }
+
+    // куда уходит вода из ячейки: вниз по склону, а со дна впадины - за ее край
+    fn receiver(&mut self, cell: [i64; 2]) -> Option<[i64; 2]> {
+        if let Some(receiver) = self.receivers.get(&cell) {
+            return *receiver;
+        }
+        let receiver = if self.is_sink(cell) {
+            None
+        } else {
+            match self.descent(cell) {
+                Some(neighbour) => Some(neighbour),
+                None => self.lake(cell).exit,
+            }
+        };
+        self.receivers.insert(cell, receiver);
+        receiver
+    }
+
+    // дно впадины, к которому спускается вода из ячейки; None, если она уходит в море
+    fn pit(&mut self, cell: [i64; 2]) -> Option<[i64; 2]> {
+        let mut path = Vec::new();
+        let mut current = cell;
+        let pit = loop {
+            if let Some(pit) = self.pits.get(&current) {
+                break *pit;
+            }
+            if self.is_sink(current) || path.len() >= MAX_DESCENT {
+                break None;
+            }
+            path.push(current);
+            match self.descent(current) {
+                Some(next) => current = next,
+                None => break Some(current),
+            }
+        };
+        for visited in path {
+            self.pits.insert(visited, pit);
+        }
+        pit
+    }
+
+    // заполняет впадину от дна, пока вода не найдет ячейку ниже дна (priority flood)
+    fn lake(&mut self, pit: [i64; 2]) -> Rc<Lake> {
+        if let Some(lake) = self.lakes.get(&pit) {
+            return lake.clone();
+        }
+        let mut level = self.height(pit);
+        let mut cells = HashSet::new();
+        let mut queued = HashSet::from([pit]);
+        let mut queue = BinaryHeap::from([FloodCell {
+            height: level,
+            cell: pit,
+        }]);
+        let mut exit = None;
+        while let Some(FloodCell { height, cell }) = queue.pop() {
+            if self.is_below(cell, pit) {
+                exit = Some(cell);
+                break;
+            }
+            if cells.len() >= MAX_LAKE_CELLS {
+                break;
+            }
+            level = level.max(height);
+            cells.insert(cell);
+            for (offset_x, offset_z, _) in NEIGHBOURS {
+                let neighbour = [cell[0] + offset_x, cell[1] + offset_z];
+                if queued.insert(neighbour) {
+                    queue.push(FloodCell {
+                        height: self.height(neighbour),
+                        cell: neighbour,
+                    });
+                }
+            }
+        }
+        let lake = Rc::new(Lake { level, exit, cells });
+        self.lakes.insert(pit, lake.clone());
+        lake
+    }
+
+    // ячейки, вода из которых приходит прямо в эту: соседи по склону и
+    // впадины, которые переливаются в нее
+    fn donors(&mut self, cell: [i64; 2]) -> Vec<[i64; 2]> {
+        let mut donors = Vec::new();
+        for (offset_x, offset_z, _) in NEIGHBOURS {
+            let neighbour = [cell[0] + offset_x, cell[1] + offset_z];
+            if self.receiver(neighbour) == Some(cell) {
+                donors.push(neighbour);
+            }
+            // переливается впадина, залившая соседа; она в цепочке озер от его дна
+            let mut pit = self.pit(neighbour);
+            for _ in 0..MAX_LAKE_CHAIN {
+                let Some(current) = pit else {
+                    break;
+                };
+                let lake = self.lake(current);
+                if lake.exit == Some(cell) && !donors.contains(&current) {
+                    donors.push(current);
+                }
+                pit = lake.exit.and_then(|exit| self.pit(exit));
+            }
+        }
+        donors
+    }
+
+    // уровень озера над ячейкой: наибольший среди озер, которые ее заливают
+    fn lake_level(&mut self, cell: [i64; 2]) -> Option<f64> {
+        let mut level: Option<f64> = None;
+        let mut pit = self.pit(cell);
+        for _ in 0..MAX_LAKE_CHAIN {
+            let Some(current) = pit else {
+                break;
+            };
+            let lake = self.lake(current);
+            // нижнее озеро может залить и впадину выше себя
+            if lake.cells.contains(&cell) {
+                level = Some(level.map_or(lake.level, |level| level.max(lake.level)));
+            }
+            pit = lake.exit.and_then(|exit| self.pit(exit));
+        }
+        level
+    }
+
+    // площадь водосбора ячейки в ячейках, не больше limit. known - уже
+    // посчитанные ячейки, их водосбор не обходится заново
+    fn accumulation(&mut self, cell: [i64; 2], limit: f64, known: &HashMap<[i64; 2], f64>) -> f64 {
+        let mut total = 0.;
+        let mut queue = VecDeque::from([cell]);
+        while let Some(current) = queue.pop_front() {
+            if let Some(accumulation) = known.get(&current) {
+                total += accumulation;
+            } else {
+                total += 1.;
+                queue.extend(self.donors(current));
+            }
+            if total >= limit {
+                return limit;
+            }
+        }
+        total
+    }
+}
+
+// русла и глубина воды одного тайла, точки с шагом cell_size
+struct HydrologyTile {
+    // понижение поверхности руслом (не больше нуля)
+    carve: Vec<f32>,
+    // глубина воды над дном русла или озера
+    water: Vec<f32>,
+}
+
+/// Море, реки и озера поверх рельефа, заданного поверхностью.
+///
+/// Плотность у поверхности должна быть `surface_height - y` в метрах: русло
+/// прибавляется к ней как понижение высоты.
+///
+/// Впадины заполняются до уровня перелива и становятся озерами, вода стекает
+/// по кратчайшему спуску к морю, и там, где площадь водосбора превышает
+/// `river_threshold`, в плотности прорезается русло. Уровень воды доступен
+/// через `DensityFunction::water_level`, из него строится отдельный меш воды.
+pub struct HydrologyTerrain {
+    terrain: Arc<dyn DensityFunction>,
+    settings: HydrologySettings,
+    cache: TileCache<HydrologyTile>,
+}
+
+impl HydrologyTerrain {
+    pub fn new(terrain: Arc<dyn DensityFunction>, settings: HydrologySettings) -> Self {
+        HydrologyTerrain {
+            terrain,
+            cache: TileCache::new(settings.cache_capacity),
+            settings,
+        }
+    }
+
+    pub fn settings(&self) -> &HydrologySettings {
+        &self.settings
+    }
+
+    // тайл хранит точки своих ячеек вместе с дальним краем: (tile_cells + 1)^2
+    fn simulate_tile(&self, tile: [i64; 2]) -> HydrologyTile {
+        let settings = &self.settings;
+        let cells = settings.tile_cells as i64;
+        let side = settings.tile_cells + 1;
+        // считается с полем в ячейку для размытия русел
+        let margin_side = side + 2;
+        let start = [tile[0] * cells - 1, tile[1] * cells - 1];
+        let mut drainage = Drainage::new(self.terrain.as_ref(), settings);
+
+        let mut region = Vec::with_capacity(margin_side * margin_side);
+        for z in 0..margin_side as i64 {
+            for x in 0..margin_side as i64 {
+                let cell = [start[0] + x, start[1] + z];
+                region.push((drainage.height(cell), cell));
+            }
+        }
+        // сверху вниз по течению: водосбор ячейки складывается из уже посчитанных
+        let mut order = region.clone();
+        order.sort_by(|first, second| compare_cells(*second, *first));
+        let limit = settings.saturated_accumulation();
+        let mut accumulation = HashMap::with_capacity(order.len());
+        for (height, cell) in order {
+            if height <= settings.sea_level {
+                continue;
+            }
+            let mut total = 1.;
+            for donor in drainage.donors(cell) {
+                if total >= limit {
+                    break;
+                }
+                total += drainage.accumulation(donor, limit - total, &accumulation);
+            }
+            accumulation.insert(cell, total.min(limit));
+        }
+
+        let mut carve = vec![0.; region.len()];
+        let mut river = vec![0.; region.len()];
+        let mut lake = vec![0.; region.len()];
+        for (index, (height, cell)) in region.iter().enumerate() {
+            if *height <= settings.sea_level {
+                continue;
+            }
+            let lake_depth = drainage
+                .lake_level(*cell)
+                .map_or(0., |level| level - height);
+            let cell_accumulation = accumulation.get(cell).copied().unwrap_or(0.);
+            if lake_depth >= settings.min_lake_depth {
+                lake[index] = lake_depth;
+            } else if cell_accumulation >= settings.river_threshold {
+                let strength = settings.river_strength(cell_accumulation);
+                carve[index] = -settings.river_depth * strength;
+                river[index] = settings.river_depth * strength * settings.river_fill;
+            }
+        }
+
+        // поверхность озера должна остаться ровной, поэтому размываются только реки
+        let carve = blur(&carve, margin_side);
+        let river = blur(&river, margin_side);
+        let mut tile_carve = Vec::with_capacity(side * side);
+        let mut tile_water = Vec::with_capacity(side * side);
+        for z in 1..=side {
+            for x in 1..=side {
+                let index = x + z * margin_side;
+                tile_carve.push(carve[index] as f32);
+                tile_water.push(river[index].max(lake[index]) as f32);
+            }
+        }
+        HydrologyTile {
+            carve: tile_carve,
+            water: tile_water,
+        }
+    }
+
+    // билинейная выборка слоя тайла, в котором лежит точка
+    fn sample_layer(&self, position: [f64; 2], layer: impl Fn(&HydrologyTile) -> &[f32]) -> f64 {
+        let cells = self.settings.tile_cells;
+        let tile_size = cells as f64 * self.settings.cell_size;
+        let tile = [
+            (position[0] / tile_size).floor() as i64,
+            (position[1] / tile_size).floor() as i64,
+        ];
+        let hydrology_tile = self
+            .cache
+            .get_or_insert_with(tile, || self.simulate_tile(tile));
+        let values = layer(&hydrology_tile);
+        let max = cells as f64 - 1e-9;
+        let x =
+            ((position[0] - tile[0] as f64 * tile_size) / self.settings.cell_size).clamp(0., max);
+        let z =
+            ((position[1] - tile[1] as f64 * tile_size) / self.settings.cell_size).clamp(0., max);
+        let (cell_x, cell_z) = (x.floor() as usize, z.floor() as usize);
+        let (u, v) = (x - cell_x as f64, z - cell_z as f64);
+        let side = cells + 1;
+        let read = |x: usize, z: usize| values[x + z * side] as f64;
+        read(cell_x, cell_z) * (1. - u) * (1. - v)
+            + read(cell_x + 1, cell_z) * u * (1. - v)
+            + read(cell_x, cell_z + 1) * (1. - u) * v
+            + read(cell_x + 1, cell_z + 1) * u * v
+    }
+
+    // понижение поверхности руслами рек в метрах
+    pub fn river_carve(&self, position: [f64; 2]) -> f64 {
+        self.sample_layer(position, |tile| &tile.carve)
+    }
+
+    // глубина рек и озер над дном
+    pub fn water_depth(&self, position: [f64; 2]) -> f64 {
+        self.sample_layer(position, |tile| &tile.water)
+    }
+}
+
+impl DensityFunction for HydrologyTerrain {
+    fn sample(&self, position: [f64; 3]) -> f64 {
+        self.terrain.sample(position) + self.river_carve([position[0], position[2]])
+    }
+
+    fn sample_column(&self, column: [f64; 2], heights: &[f64], densities: &mut [f64]) {
+        self.terrain.sample_column(column, heights, densities);
+        let carve = self.river_carve(column);
+        for density in densities.iter_mut() {
+            *density += carve;
+        }
+    }
+
+    fn material(&self, position: [f64; 3]) -> VoxelMaterial {
+        // дно и берега водоемов песчаные
+        match self.water_level([position[0], position[2]]) {
+            Some(level) if position[1] <= level + 1. => VoxelMaterial::Sand,
+            _ => self.terrain.material(position),
+        }
+    }
+
+    fn surface_height(&self, position: [f64; 2]) -> Option<f64> {
+        self.terrain
+            .surface_height(position)
+            .map(|height| height + self.river_carve(position))
+    }
+
+    fn water_level(&self, position: [f64; 2]) -> Option<f64> {
+        let ground = self.surface_height(position)?;
+        let depth = self.water_depth(position);
+        let inland = (depth > MIN_WATER_DEPTH).then_some(ground + depth);
+        let sea = (ground < self.settings.sea_level).then_some(self.settings.sea_level);
+        match (inland, sea) {
+            (Some(inland), Some(sea)) => Some(inland.max(sea)),
+            (inland, sea) => inland.or(sea),
+        }
+    }
+
+    fn up(&self, position: [f64; 3]) -> [f64; 3] {
+        self.terrain.up(position)
+    }
+}
+
+// размытие 3x3, расширяет русла до нескольких ячеек и сглаживает их берега
+fn blur(values: &[f64], side: usize) -> Vec<f64> {
+    let mut result = vec![0.; values.len()];
+    for z in 0..side {
+        for x in 0..side {
+            let mut sum = 0.;
+            let mut count = 0.;
+            for neighbour_z in z.saturating_sub(1)..(z + 2).min(side) {
+                for neighbour_x in x.saturating_sub(1)..(x + 2).min(side) {
+                    sum += values[neighbour_x + neighbour_z * side];
+                    count += 1.;
+                }
+            }
+            result[x + z * side] = sum / count;
+        }
+    }
+    result
+}
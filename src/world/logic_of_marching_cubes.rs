@@ -4,6 +4,7 @@ use super::data_for_marching_cubes::{
     EDGES_FOR_MARCHING_CUBES, POINTS_FOR_MARCHING_CUBES, TRIANGULATIONS_FOR_MARCHING_CUBES,
 };
 
+#[derive(Clone)]
 pub struct VoxelGrid {
     data: Vec<f32>,
     // число вокселей у сетки (сторона куба/чанка)
@@ -18,19 +19,60 @@ impl VoxelGrid {
         }
     }
 
+    // сетка из готовых значений в порядке x, затем y, затем z
+    pub fn from_data(resolution: usize, data: Vec<f32>) -> Self {
+        assert_eq!(data.len(), resolution * resolution * resolution);
+        Self { data, resolution }
+    }
+
     pub fn read(&self, x: usize, y: usize, z: usize) -> f32 {
         self.data[x + y * self.resolution + z * self.resolution * self.resolution]
     }
 
+    pub fn write(&mut self, x: usize, y: usize, z: usize, value: f32) {
+        self.data[x + y * self.resolution + z * self.resolution * self.resolution] = value;
+    }
+
     pub fn push(&mut self, value: f32) {
         self.data.push(value);
     }
+
+    pub fn resolution(&self) -> usize {
+        self.resolution
+    }
+
+    // значения плотности в порядке x, затем y, затем z
+    pub fn data(&self) -> &[f32] {
+        &self.data
+    }
+
+    // сетка меньшего разрешения из каждой n-й точки этой же сетки. Возможна, только если
+    // число ячеек новой сетки делит число ячеек этой, тогда точки совпадают в точности
+    pub fn downsample(&self, resolution: usize) -> Option<VoxelGrid> {
+        if resolution < 2 || resolution > self.resolution {
+            return None;
+        }
+        let cells = self.resolution - 1;
+        if !cells.is_multiple_of(resolution - 1) {
+            return None;
+        }
+        let step = cells / (resolution - 1);
+        let mut grid = VoxelGrid::new(resolution);
+        for z in 0..resolution {
+            for y in 0..resolution {
+                for x in 0..resolution {
+                    grid.push(self.read(x * step, y * step, z * step));
+                }
+            }
+        }
+        Some(grid)
+    }
 }
 
 fn get_triangulation(voxel_grid: &VoxelGrid, (x, y, z): (usize, usize, usize)) -> [i8; 15] {
     let mut config_idx = 0b00000000;
 
-    config_idx |= (voxel_grid.read(x, y, z).is_sign_negative() as u8) << 0;
+    config_idx |= voxel_grid.read(x, y, z).is_sign_negative() as u8;
     config_idx |= (voxel_grid.read(x, y, z + 1).is_sign_negative() as u8) << 1;
     config_idx |= (voxel_grid.read(x + 1, y, z + 1).is_sign_negative() as u8) << 2;
     config_idx |= (voxel_grid.read(x + 1, y, z).is_sign_negative() as u8) << 3;
@@ -39,7 +81,7 @@ fn get_triangulation(voxel_grid: &VoxelGrid, (x, y, z): (usize, usize, usize)) -
     config_idx |= (voxel_grid.read(x + 1, y + 1, z + 1).is_sign_negative() as u8) << 6;
     config_idx |= (voxel_grid.read(x + 1, y + 1, z).is_sign_negative() as u8) << 7;
 
-    return TRIANGULATIONS_FOR_MARCHING_CUBES[config_idx as usize];
+    TRIANGULATIONS_FOR_MARCHING_CUBES[config_idx as usize]
 }
 
 pub fn march_cube(
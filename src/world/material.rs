@@ -0,0 +1,34 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Материал вокселя. Определяет цвет вершин меша в этой точке.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum VoxelMaterial {
+    #[default]
+    Grass,
+    Dirt,
+    Rock,
+    Sand,
+    Snow,
+    // глубинная порода под основным слоем камня
+    Basalt,
+    Coal,
+    Iron,
+    Gold,
+}
+
+impl VoxelMaterial {
+    pub fn color(&self) -> Color {
+        match self {
+            VoxelMaterial::Grass => Color::rgb(0.28, 0.52, 0.2),
+            VoxelMaterial::Dirt => Color::rgb(0.42, 0.3, 0.2),
+            VoxelMaterial::Rock => Color::rgb(0.45, 0.43, 0.41),
+            VoxelMaterial::Sand => Color::rgb(0.86, 0.78, 0.55),
+            VoxelMaterial::Snow => Color::rgb(0.93, 0.95, 0.97),
+            VoxelMaterial::Basalt => Color::rgb(0.22, 0.22, 0.25),
+            VoxelMaterial::Coal => Color::rgb(0.1, 0.1, 0.1),
+            VoxelMaterial::Iron => Color::rgb(0.62, 0.42, 0.32),
+            VoxelMaterial::Gold => Color::rgb(0.95, 0.78, 0.2),
+        }
+    }
+}
@@ -0,0 +1,314 @@
+use bevy::{
+    asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+};
+use noise::{
+    Abs, Add, BasicMulti, Clamp, Constant, Fbm, Max, Min, MultiFractal, Multiply, NoiseFn, Perlin,
+    RidgedMulti, ScaleBias, ScalePoint, Seedable, Select, SuperSimplex, Turbulence,
+};
+use serde::Deserialize;
+
+use super::density::{DensityFunction, WorldDensity};
+use super::world_config::{TerrainScale, WorldConfig, WorldSeed};
+
+pub type BoxedNoise = Box<dyn NoiseFn<f64, 3> + Send + Sync>;
+
+/// Параметры фрактального шума, общие для Fbm, BasicMulti и RidgedMulti.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct FractalSettings {
+    pub octaves: usize,
+    pub frequency: f64,
+    pub lacunarity: f64,
+    pub persistence: f64,
+}
+
+impl Default for FractalSettings {
+    fn default() -> Self {
+        FractalSettings {
+            octaves: 6,
+            frequency: 1.,
+            lacunarity: std::f64::consts::PI * 2. / 3.,
+            persistence: 0.5,
+        }
+    }
+}
+
+/// Узел графа шума. Каждый вариант соответствует модулю крейта `noise`.
+///
+/// Генераторы не хранят зерно напрямую: `layer` - имя слоя, из которого
+/// выводится под-зерно мира, поэтому один граф дает разные миры при разных зернах.
+#[derive(Deserialize, Clone, Debug)]
+pub enum NoiseNode {
+    Constant(f64),
+    SuperSimplex {
+        layer: String,
+    },
+    Perlin {
+        layer: String,
+    },
+    Fbm {
+        layer: String,
+        #[serde(default)]
+        fractal: FractalSettings,
+    },
+    BasicMulti {
+        layer: String,
+        #[serde(default)]
+        fractal: FractalSettings,
+    },
+    RidgedMulti {
+        layer: String,
+        #[serde(default)]
+        fractal: FractalSettings,
+    },
+    Add(Box<NoiseNode>, Box<NoiseNode>),
+    Multiply(Box<NoiseNode>, Box<NoiseNode>),
+    Min(Box<NoiseNode>, Box<NoiseNode>),
+    Max(Box<NoiseNode>, Box<NoiseNode>),
+    Abs(Box<NoiseNode>),
+    Clamp {
+        source: Box<NoiseNode>,
+        lower: f64,
+        upper: f64,
+    },
+    ScaleBias {
+        source: Box<NoiseNode>,
+        scale: f64,
+        bias: f64,
+    },
+    ScalePoint {
+        source: Box<NoiseNode>,
+        x: f64,
+        y: f64,
+        z: f64,
+    },
+    Select {
+        first: Box<NoiseNode>,
+        second: Box<NoiseNode>,
+        control: Box<NoiseNode>,
+        lower_bound: f64,
+        upper_bound: f64,
+        #[serde(default)]
+        falloff: f64,
+    },
+    Turbulence {
+        source: Box<NoiseNode>,
+        layer: String,
+        frequency: f64,
+        power: f64,
+        roughness: usize,
+    },
+}
+
+impl NoiseNode {
+    pub fn build(&self, seed: WorldSeed) -> BoxedNoise {
+        let layer_seed = |layer: &str| seed.sub_seed(layer).noise_seed();
+        match self {
+            NoiseNode::Constant(value) => Box::new(Constant::new(*value)),
+            NoiseNode::SuperSimplex { layer } => Box::new(SuperSimplex::new(layer_seed(layer))),
+            NoiseNode::Perlin { layer } => Box::new(Perlin::new(layer_seed(layer))),
+            NoiseNode::Fbm { layer, fractal } => Box::new(
+                Fbm::<SuperSimplex>::new(layer_seed(layer))
+                    .set_octaves(fractal.octaves)
+                    .set_frequency(fractal.frequency)
+                    .set_lacunarity(fractal.lacunarity)
+                    .set_persistence(fractal.persistence),
+            ),
+            NoiseNode::BasicMulti { layer, fractal } => Box::new(
+                BasicMulti::<SuperSimplex>::new(layer_seed(layer))
+                    .set_octaves(fractal.octaves)
+                    .set_frequency(fractal.frequency)
+                    .set_lacunarity(fractal.lacunarity)
+                    .set_persistence(fractal.persistence),
+            ),
+            NoiseNode::RidgedMulti { layer, fractal } => Box::new(
+                RidgedMulti::<SuperSimplex>::new(layer_seed(layer))
+                    .set_octaves(fractal.octaves)
+                    .set_frequency(fractal.frequency)
+                    .set_lacunarity(fractal.lacunarity)
+                    .set_persistence(fractal.persistence),
+            ),
+            NoiseNode::Add(first, second) => {
+                Box::new(Add::new(first.build(seed), second.build(seed)))
+            }
+            NoiseNode::Multiply(first, second) => {
+                Box::new(Multiply::new(first.build(seed), second.build(seed)))
+            }
+            NoiseNode::Min(first, second) => {
+                Box::new(Min::new(first.build(seed), second.build(seed)))
+            }
+            NoiseNode::Max(first, second) => {
+                Box::new(Max::new(first.build(seed), second.build(seed)))
+            }
+            NoiseNode::Abs(source) => Box::new(Abs::new(source.build(seed))),
+            NoiseNode::Clamp {
+                source,
+                lower,
+                upper,
+            } => Box::new(Clamp::new(source.build(seed)).set_bounds(*lower, *upper)),
+            NoiseNode::ScaleBias {
+                source,
+                scale,
+                bias,
+            } => Box::new(
+                ScaleBias::new(source.build(seed))
+                    .set_scale(*scale)
+                    .set_bias(*bias),
+            ),
+            NoiseNode::ScalePoint { source, x, y, z } => Box::new(
+                ScalePoint::new(source.build(seed))
+                    .set_x_scale(*x)
+                    .set_y_scale(*y)
+                    .set_z_scale(*z),
+            ),
+            NoiseNode::Select {
+                first,
+                second,
+                control,
+                lower_bound,
+                upper_bound,
+                falloff,
+            } => Box::new(
+                Select::new(first.build(seed), second.build(seed), control.build(seed))
+                    .set_bounds(*lower_bound, *upper_bound)
+                    .set_falloff(*falloff),
+            ),
+            NoiseNode::Turbulence {
+                source,
+                layer,
+                frequency,
+                power,
+                roughness,
+            } => Box::new(
+                Turbulence::<_, SuperSimplex>::new(source.build(seed))
+                    .set_seed(layer_seed(layer))
+                    .set_frequency(*frequency)
+                    .set_power(*power)
+                    .set_roughness(*roughness),
+            ),
+        }
+    }
+}
+
+/// Как трактуется значение корневого узла графа.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NoiseGraphOutput {
+    // значение в точке (x, 0, z) - высота поверхности
+    Height,
+    // значение в точке (x, y, z) - сама плотность
+    Density,
+}
+
+/// Описание рельефа в виде графа шума, загружаемое из файла `*.noise.ron`.
+#[derive(Deserialize, TypeUuid, TypePath, Clone, Debug)]
+#[uuid = "6f2c1a9e-3b7d-4e58-9c41-0d8a5e7b2f13"]
+pub struct NoiseGraph {
+    pub output: NoiseGraphOutput,
+    pub root: NoiseNode,
+}
+
+impl NoiseGraph {
+    pub fn from_ron(source: &str) -> Result<Self, ron::error::SpannedError> {
+        ron::from_str(source)
+    }
+
+    pub fn build(&self, seed: WorldSeed, scale: TerrainScale) -> NoiseGraphDensity {
+        NoiseGraphDensity {
+            output: self.output,
+            noise: self.root.build(seed),
+            scale,
+        }
+    }
+}
+
+/// Функция плотности, собранная из `NoiseGraph`.
+pub struct NoiseGraphDensity {
+    output: NoiseGraphOutput,
+    noise: BoxedNoise,
+    scale: TerrainScale,
+}
+
+impl DensityFunction for NoiseGraphDensity {
+    fn sample(&self, position: [f64; 3]) -> f64 {
+        let noise_position = self.scale.to_noise_space(position);
+        match self.output {
+            NoiseGraphOutput::Height => {
+                self.noise.get([noise_position[0], 0., noise_position[2]]) * self.scale.vertical
+                    - position[1]
+            }
+            NoiseGraphOutput::Density => self.noise.get(noise_position),
+        }
+    }
+
+    fn sample_column(&self, column: [f64; 2], heights: &[f64], densities: &mut [f64]) {
+        let NoiseGraphOutput::Height = self.output else {
+            for (height, density) in heights.iter().zip(densities.iter_mut()) {
+                *density = self.sample([column[0], *height, column[1]]);
+            }
+            return;
+        };
+        let noise_position = self.scale.to_noise_space([column[0], 0., column[1]]);
+        let surface =
+            self.noise.get([noise_position[0], 0., noise_position[2]]) * self.scale.vertical;
+        for (height, density) in heights.iter().zip(densities.iter_mut()) {
+            *density = surface - height;
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct NoiseGraphLoader;
+
+impl AssetLoader for NoiseGraphLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let graph: NoiseGraph = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(graph));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["noise.ron"]
+    }
+}
+
+/// Граф шума, который используется как рельеф мира.
+///
+/// После загрузки (и после каждого изменения файла при включенном
+/// `AssetPlugin::watch_for_changes`) граф заменяет `WorldDensity`.
+#[derive(Resource)]
+pub struct WorldNoiseGraph(pub Handle<NoiseGraph>);
+
+pub fn apply_noise_graph(
+    mut asset_events: EventReader<AssetEvent<NoiseGraph>>,
+    world_noise_graph: Option<Res<WorldNoiseGraph>>,
+    noise_graphs: Res<Assets<NoiseGraph>>,
+    config: Res<WorldConfig>,
+    mut world_density: ResMut<WorldDensity>,
+) {
+    let Some(world_noise_graph) = world_noise_graph else {
+        asset_events.clear();
+        return;
+    };
+
+    let graph_changed = asset_events.iter().any(|event| match event {
+        AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+            *handle == world_noise_graph.0
+        }
+        AssetEvent::Removed { .. } => false,
+    });
+    if !graph_changed {
+        return;
+    }
+
+    if let Some(graph) = noise_graphs.get(&world_noise_graph.0) {
+        *world_density = WorldDensity::new(graph.build(config.seed, config.terrain_scale));
+    }
+}
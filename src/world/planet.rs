@@ -0,0 +1,178 @@
+use bevy::prelude::*;
+use noise::{Fbm, MultiFractal, NoiseFn, SuperSimplex};
+
+use super::chunk_from_marching_cubes::VoxelChunk;
+use super::density::{DensityFunction, WorldDensity};
+use super::floating_origin::FloatingOrigin;
+use super::material::VoxelMaterial;
+use super::world_config::WorldSeed;
+
+/// Настройки планеты. Расстояния в метрах.
+#[derive(Clone, Debug)]
+pub struct PlanetSettings {
+    // центр планеты в мировых координатах
+    pub centre: [f64; 3],
+    // радиус поверхности без рельефа
+    pub radius: f64,
+    // насколько рельеф поднимается над радиусом и опускается под него
+    pub displacement: f64,
+    // характерный размер холмов на поверхности
+    pub noise_scale: f64,
+}
+
+impl Default for PlanetSettings {
+    fn default() -> Self {
+        // центр под началом координат, чтобы камера появлялась на северном полюсе
+        PlanetSettings {
+            centre: [0., -1000., 0.],
+            radius: 1000.,
+            displacement: 48.,
+            noise_scale: 256.,
+        }
+    }
+}
+
+impl PlanetSettings {
+    // координаты чанков, которые пересекают слой рельефа вокруг сферы
+    pub fn shell_chunks(&self) -> Vec<[i128; 3]> {
+        let size = VoxelChunk::size_chunk() as f64;
+        let inner = self.radius - self.displacement;
+        let outer = self.radius + self.displacement;
+        let range = |axis: usize| {
+            let min = ((self.centre[axis] - outer) / size).floor() as i128;
+            let max = ((self.centre[axis] + outer) / size).floor() as i128;
+            min..=max
+        };
+        let mut chunks = Vec::new();
+        for z in range(2) {
+            for y in range(1) {
+                for x in range(0) {
+                    let coordinates = [x, y, z];
+                    // ближайшая и самая дальняя от центра точки чанка
+                    let mut nearest = 0.;
+                    let mut farthest = 0.;
+                    for (coordinate, centre) in coordinates.iter().zip(self.centre) {
+                        let min = *coordinate as f64 * size - centre;
+                        let max = min + size;
+                        let near = if min > 0. {
+                            min
+                        } else if max < 0. {
+                            -max
+                        } else {
+                            0.
+                        };
+                        let far = min.abs().max(max.abs());
+                        nearest += near * near;
+                        farthest += far * far;
+                    }
+                    if nearest.sqrt() <= outer && farthest.sqrt() >= inner {
+                        chunks.push(coordinates);
+                    }
+                }
+            }
+        }
+        chunks
+    }
+}
+
+/// Сферический рельеф: плотность задается по радиусу от центра планеты.
+///
+/// Высота поверхности зависит только от направления из центра, поэтому рельеф
+/// одинаково выглядит на любой стороне сферы. `surface_height` не определена,
+/// глубину и направление "вверх" отвечают `depth` и `up`. Слои, которым нужна
+/// поверхность над плоскостью (эрозия, реки, постройки), в режиме планеты не работают.
+pub struct PlanetTerrain {
+    noise: Fbm<SuperSimplex>,
+    settings: PlanetSettings,
+}
+
+impl PlanetTerrain {
+    pub fn new(seed: WorldSeed, settings: PlanetSettings) -> Self {
+        PlanetTerrain {
+            noise: Fbm::<SuperSimplex>::new(seed.sub_seed("planet").noise_seed()).set_octaves(5),
+            settings,
+        }
+    }
+
+    pub fn settings(&self) -> &PlanetSettings {
+        &self.settings
+    }
+
+    // (направление из центра, расстояние до центра)
+    fn polar(&self, position: [f64; 3]) -> ([f64; 3], f64) {
+        let offset = [
+            position[0] - self.settings.centre[0],
+            position[1] - self.settings.centre[1],
+            position[2] - self.settings.centre[2],
+        ];
+        let distance =
+            (offset[0] * offset[0] + offset[1] * offset[1] + offset[2] * offset[2]).sqrt();
+        if distance == 0. {
+            return ([0., 1., 0.], 0.);
+        }
+        (offset.map(|value| value / distance), distance)
+    }
+
+    // расстояние от центра до поверхности в направлении direction
+    pub fn surface_radius(&self, direction: [f64; 3]) -> f64 {
+        let scale = self.settings.radius / self.settings.noise_scale;
+        self.settings.radius
+            + self.noise.get(direction.map(|value| value * scale)) * self.settings.displacement
+    }
+}
+
+impl DensityFunction for PlanetTerrain {
+    fn sample(&self, position: [f64; 3]) -> f64 {
+        let (direction, distance) = self.polar(position);
+        self.surface_radius(direction) - distance
+    }
+
+    fn material(&self, position: [f64; 3]) -> VoxelMaterial {
+        let (direction, _) = self.polar(position);
+        let altitude =
+            (self.surface_radius(direction) - self.settings.radius) / self.settings.displacement;
+        if altitude > 0.55 {
+            VoxelMaterial::Snow
+        } else if altitude < -0.25 {
+            VoxelMaterial::Sand
+        } else {
+            VoxelMaterial::Grass
+        }
+    }
+
+    fn depth(&self, position: [f64; 3]) -> Option<f64> {
+        Some(self.sample(position))
+    }
+
+    fn up(&self, position: [f64; 3]) -> [f64; 3] {
+        self.polar(position).0
+    }
+}
+
+/// Локальное направление "вверх" в точке, где стоит сущность.
+///
+/// `WorldPlugin` обновляет его каждый кадр по `WorldDensity::up`: на плоском мире
+/// это всегда `Vec3::Y`, на планете - направление от ее центра. По нему ориентируется
+/// `FlyCamera`, а физика может брать отсюда направление силы тяжести (`-up`).
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct LocalUp(pub Vec3);
+
+impl Default for LocalUp {
+    fn default() -> Self {
+        LocalUp(Vec3::Y)
+    }
+}
+
+pub(crate) fn update_local_up(
+    floating_origin: Res<FloatingOrigin>,
+    world_density: Res<WorldDensity>,
+    mut local_up_q: Query<(&GlobalTransform, &mut LocalUp)>,
+) {
+    for (transform, mut local_up) in local_up_q.iter_mut() {
+        let up = world_density.up(floating_origin.scene_to_world(transform.translation()));
+        // не трогаем компонент без нужды, чтобы не срабатывало Changed<LocalUp>
+        if local_up.0 != up {
+            local_up.0 = up;
+        }
+    }
+}
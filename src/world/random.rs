@@ -0,0 +1,41 @@
+// детерминированный генератор случайных чисел: одно зерно - одна последовательность
+// на любой платформе и при любом порядке загрузки чанков
+
+// финальное перемешивание splitmix64
+pub(crate) fn mix(value: u64) -> u64 {
+    let mut value = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    value ^ (value >> 31)
+}
+
+// зерно для ячейки с целыми координатами
+pub(crate) fn hash_coordinates(seed: u64, coordinates: &[i64]) -> u64 {
+    coordinates
+        .iter()
+        .fold(mix(seed), |hash, value| mix(hash ^ *value as u64))
+}
+
+pub(crate) struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub(crate) fn new(seed: u64) -> Self {
+        Random { state: seed }
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        mix(self.state)
+    }
+
+    // равномерно в [0, 1)
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    pub(crate) fn range(&mut self, min: f64, max: f64) -> f64 {
+        min + (max - min) * self.next_f64()
+    }
+}
@@ -0,0 +1,173 @@
+use bevy::prelude::*;
+
+use super::chunk_from_marching_cubes::VoxelChunk;
+use super::density::{DensityFunction, WorldDensity};
+use super::editing::VoxelEdits;
+use super::floating_origin::FloatingOrigin;
+use super::material::VoxelMaterial;
+use crate::fly_camera::FlyCamera;
+
+// шагов деления отрезка со сменой знака: точность около 1 м / 2^20
+const REFINE_STEPS: usize = 20;
+// шаг разностей для нормали, метры
+const NORMAL_EPSILON: f64 = 0.05;
+// дальность выбора рельефа прицелом камеры, метры
+const CROSSHAIR_DISTANCE: f64 = 64.;
+
+/// Попадание луча в рельеф. Координаты мировые, в метрах.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RayHit {
+    pub position: [f64; 3],
+    // наружу из земли
+    pub normal: [f64; 3],
+    // расстояние от начала луча
+    pub distance: f64,
+    pub chunk: [i128; 3],
+    pub material: VoxelMaterial,
+}
+
+/// Рельеф под прицелом камеры, обновляется каждый кадр.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct CrosshairTarget(pub Option<RayHit>);
+
+// ищет первую точку, где луч входит в землю (плотность становится положительной).
+// Луч идет по ячейкам сетки правок с шагом в метр (DDA), плотность берется на
+// границах ячеек, а отрезок со сменой знака уточняется делением пополам
+pub fn raycast(
+    density: &dyn DensityFunction,
+    origin: [f64; 3],
+    direction: [f64; 3],
+    max_distance: f64,
+) -> Option<RayHit> {
+    let length = direction
+        .iter()
+        .map(|value| value * value)
+        .sum::<f64>()
+        .sqrt();
+    if length == 0. || max_distance < 0. {
+        return None;
+    }
+    let direction = direction.map(|value| value / length);
+    let at = |t: f64| [0, 1, 2].map(|axis| origin[axis] + direction[axis] * t);
+    let value_at = |t: f64| density.sample(at(t));
+
+    let mut value = value_at(0.);
+    if value > 0. {
+        return Some(hit(density, at(0.), 0.));
+    }
+
+    // расстояние вдоль луча до следующей границы ячейки и между границами по каждой оси
+    let mut next = [0, 1, 2].map(|axis| {
+        if direction[axis] == 0. {
+            f64::INFINITY
+        } else {
+            let cell = origin[axis].floor();
+            let border = if direction[axis] > 0. {
+                cell + 1.
+            } else {
+                cell
+            };
+            (border - origin[axis]) / direction[axis]
+        }
+    });
+    let delta = direction.map(|value| (1. / value).abs());
+
+    let mut t = 0.;
+    while t < max_distance {
+        let axis = (0..3)
+            .min_by(|first, second| next[*first].total_cmp(&next[*second]))
+            .unwrap();
+        let t_next = next[axis].min(max_distance);
+        let value_next = value_at(t_next);
+        if value_next > 0. {
+            let t_hit = refine(&value_at, t, t_next, value);
+            return Some(hit(density, at(t_hit), t_hit));
+        }
+        t = t_next;
+        value = value_next;
+        next[axis] += delta[axis];
+    }
+    None
+}
+
+// делит отрезок [low, high] пополам, пока не найдет смену знака плотности
+fn refine(value_at: &impl Fn(f64) -> f64, mut low: f64, mut high: f64, mut value_low: f64) -> f64 {
+    for _ in 0..REFINE_STEPS {
+        let middle = (low + high) * 0.5;
+        let value = value_at(middle);
+        if value > 0. {
+            high = middle;
+        } else {
+            low = middle;
+            value_low = value;
+        }
+    }
+    // внутри последнего отрезка - линейная интерполяция по значениям на концах
+    let value_high = value_at(high);
+    if value_high - value_low > 0. {
+        low + (high - low) * (-value_low / (value_high - value_low)).clamp(0., 1.)
+    } else {
+        high
+    }
+}
+
+fn hit(density: &dyn DensityFunction, position: [f64; 3], distance: f64) -> RayHit {
+    // плотность растет в глубь земли, нормаль направлена против градиента
+    let gradient = [0, 1, 2].map(|axis| {
+        let mut forward = position;
+        let mut back = position;
+        forward[axis] += NORMAL_EPSILON;
+        back[axis] -= NORMAL_EPSILON;
+        density.sample(back) - density.sample(forward)
+    });
+    let length = gradient
+        .iter()
+        .map(|value| value * value)
+        .sum::<f64>()
+        .sqrt();
+    let normal = if length > 0. {
+        gradient.map(|value| value / length)
+    } else {
+        density.up(position)
+    };
+    let size = VoxelChunk::size_chunk() as f64;
+    RayHit {
+        position,
+        normal,
+        distance,
+        chunk: position.map(|value| (value / size).floor() as i128),
+        material: density.material(position),
+    }
+}
+
+impl VoxelEdits {
+    // луч по миру с учетом правок и покраски
+    pub fn raycast(
+        &self,
+        density: &dyn DensityFunction,
+        origin: [f64; 3],
+        direction: [f64; 3],
+        max_distance: f64,
+    ) -> Option<RayHit> {
+        raycast(&self.over(density), origin, direction, max_distance)
+    }
+}
+
+// обновляет рельеф под прицелом камер с `CrosshairTarget`
+pub(crate) fn update_crosshair_target(
+    floating_origin: Res<FloatingOrigin>,
+    world_density: Res<WorldDensity>,
+    voxel_edits: Res<VoxelEdits>,
+    mut camera_q: Query<(&GlobalTransform, &mut CrosshairTarget), With<FlyCamera>>,
+) {
+    for (transform, mut target) in camera_q.iter_mut() {
+        let origin = floating_origin.scene_to_world(transform.translation());
+        let direction = transform.forward();
+        target.0 = voxel_edits.raycast(
+            world_density.function(),
+            origin,
+            direction.to_array().map(|value| value as f64),
+            CROSSHAIR_DISTANCE,
+        );
+    }
+}
@@ -0,0 +1,211 @@
+use noise::{NoiseFn, SuperSimplex};
+
+use super::caves::smoothstep;
+use super::density::DensityFunction;
+use super::editing::VoxelEdits;
+use super::material::VoxelMaterial;
+use super::random::hash_coordinates;
+
+/// Спад силы кисти от центра к краю.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Falloff {
+    // одинаковая сила по всей сфере
+    Constant,
+    Linear,
+    #[default]
+    Smooth,
+    // сила быстро падает уже у центра
+    Sharp,
+}
+
+impl Falloff {
+    // вес точки на расстоянии t (0 - центр, 1 - край кисти)
+    pub fn weight(&self, t: f64) -> f64 {
+        let t = t.clamp(0., 1.);
+        match self {
+            Falloff::Constant => 1.,
+            Falloff::Linear => 1. - t,
+            Falloff::Smooth => 1. - smoothstep(0., 1., t),
+            Falloff::Sharp => (1. - t) * (1. - t),
+        }
+    }
+}
+
+/// Сферическая кисть лепки. Расстояния в метрах, strength от 0 до 1.
+#[derive(Clone, Copy, Debug)]
+pub struct SculptBrush {
+    pub center: [f64; 3],
+    pub radius: f64,
+    pub falloff: Falloff,
+    pub strength: f64,
+}
+
+impl SculptBrush {
+    pub fn new(center: [f64; 3], radius: f64) -> Self {
+        SculptBrush {
+            center,
+            radius,
+            falloff: Falloff::default(),
+            strength: 1.,
+        }
+    }
+
+    pub fn with_falloff(mut self, falloff: Falloff) -> Self {
+        self.falloff = falloff;
+        self
+    }
+
+    pub fn with_strength(mut self, strength: f64) -> Self {
+        self.strength = strength;
+        self
+    }
+
+    pub fn bounds(&self) -> ([f64; 3], [f64; 3]) {
+        (
+            self.center.map(|value| value - self.radius),
+            self.center.map(|value| value + self.radius),
+        )
+    }
+
+    // сила кисти в точке с учетом спада; 0 вне сферы
+    pub fn weight(&self, position: [f64; 3]) -> f64 {
+        let distance = (0..3)
+            .map(|axis| (position[axis] - self.center[axis]).powi(2))
+            .sum::<f64>()
+            .sqrt();
+        if distance > self.radius || self.radius <= 0. {
+            return 0.;
+        }
+        self.strength.clamp(0., 1.) * self.falloff.weight(distance / self.radius)
+    }
+}
+
+/// Инструмент лепки.
+#[derive(Clone, Copy, Debug)]
+pub enum SculptTool {
+    // сглаживает плотность средним по соседним точкам
+    Smooth,
+    // выравнивает рельеф по плоскости через центр кисти с нормалью normal
+    Flatten {
+        normal: [f64; 3],
+    },
+    // выравнивает рельеф по высоте height
+    Level {
+        height: f64,
+    },
+    // добавляет к плотности шум
+    Noise {
+        amplitude: f64,
+        scale: f64,
+        seed: u32,
+    },
+    // меняет материал, не трогая форму
+    Paint(VoxelMaterial),
+}
+
+impl VoxelEdits {
+    // применяет инструмент лепки и возвращает измененные чанки
+    pub fn sculpt(
+        &mut self,
+        density: &dyn DensityFunction,
+        brush: &SculptBrush,
+        tool: &SculptTool,
+    ) -> Vec<[i128; 3]> {
+        self.record(|edits| edits.sculpt_step(density, brush, tool))
+    }
+
+    fn sculpt_step(
+        &mut self,
+        density: &dyn DensityFunction,
+        brush: &SculptBrush,
+        tool: &SculptTool,
+    ) -> Vec<[i128; 3]> {
+        let (min, max) = brush.bounds();
+        match *tool {
+            SculptTool::Smooth => self.edit_points(density, min, max, |edits, point, old| {
+                let weight = brush.weight(point.map(|value| value as f64));
+                if weight == 0. {
+                    return None;
+                }
+                // среднее по самой точке и шести соседям
+                let mut sum = old as f64;
+                for axis in 0..3 {
+                    for step in [-1, 1] {
+                        let mut neighbour = point;
+                        neighbour[axis] += step;
+                        sum += edits.value_at(density, neighbour) as f64;
+                    }
+                }
+                Some(blend(old, sum / 7., weight))
+            }),
+            SculptTool::Flatten { normal } => {
+                let length = normal.iter().map(|value| value * value).sum::<f64>().sqrt();
+                if length == 0. {
+                    return Vec::new();
+                }
+                let normal = normal.map(|value| value / length);
+                self.edit_points(density, min, max, |_, point, old| {
+                    let position = point.map(|value| value as f64);
+                    let weight = brush.weight(position);
+                    // над плоскостью пусто, под ней твердо
+                    let target = -(0..3)
+                        .map(|axis| (position[axis] - brush.center[axis]) * normal[axis])
+                        .sum::<f64>();
+                    (weight > 0.).then(|| blend(old, target, weight))
+                })
+            }
+            SculptTool::Level { height } => self.edit_points(density, min, max, |_, point, old| {
+                let weight = brush.weight(point.map(|value| value as f64));
+                (weight > 0.).then(|| blend(old, height - point[1] as f64, weight))
+            }),
+            SculptTool::Noise {
+                amplitude,
+                scale,
+                seed,
+            } => {
+                // при нулевом масштабе шум дает inf и NaN
+                if scale.is_nan() || scale <= 0. || !amplitude.is_finite() {
+                    return Vec::new();
+                }
+                let noise = SuperSimplex::new(seed);
+                self.edit_points(density, min, max, |_, point, old| {
+                    let position = point.map(|value| value as f64);
+                    let weight = brush.weight(position);
+                    (weight > 0.).then(|| {
+                        let offset = noise.get(position.map(|value| value / scale)) * amplitude;
+                        (old as f64 + offset * weight) as f32
+                    })
+                })
+            }
+            SculptTool::Paint(material) => {
+                self.paint_passes += 1;
+                let salt = self.paint_passes;
+                let mut touched = Vec::new();
+                let low = min.map(|value| value.ceil() as i64);
+                let high = max.map(|value| value.floor() as i64);
+                for z in low[2]..=high[2] {
+                    for y in low[1]..=high[1] {
+                        for x in low[0]..=high[0] {
+                            let weight = brush.weight([x as f64, y as f64, z as f64]);
+                            // слабая кисть красит часть точек, а не смешивает материалы;
+                            // каждый проход выбирает свои точки
+                            let dither =
+                                hash_coordinates(salt, &[x, y, z]) as f64 / u64::MAX as f64;
+                            if weight > 0. && dither < weight {
+                                touched.extend(self.paint_point([x, y, z], material));
+                            }
+                        }
+                    }
+                }
+                touched.sort_unstable();
+                touched.dedup();
+                touched
+            }
+        }
+    }
+}
+
+fn blend(old: f32, target: f64, weight: f64) -> f32 {
+    let old = old as f64;
+    (old + (target - old) * weight) as f32
+}
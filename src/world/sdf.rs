@@ -0,0 +1,366 @@
+use std::sync::Arc;
+
+use bevy::math::{DVec2, DVec3};
+
+use super::density::DensityFunction;
+use super::material::VoxelMaterial;
+
+// на каком расстоянии от поверхности фигуры вершины получают ее материал
+const MATERIAL_MARGIN: f64 = 1.;
+
+/// Поле расстояний со знаком: отрицательно внутри фигуры, положительно снаружи.
+/// Расстояния в метрах мира.
+///
+/// В отличие от `DensityFunction` знак обратный, поэтому фигура превращается
+/// в плотность через `SdfDensity` или вставляется в рельеф через `SdfTerrain`.
+pub trait Sdf: Send + Sync {
+    fn distance(&self, position: [f64; 3]) -> f64;
+
+    fn union<B: Sdf>(self, other: B) -> Union<Self, B>
+    where
+        Self: Sized,
+    {
+        Union(self, other)
+    }
+
+    // вырезает other из фигуры
+    fn subtract<B: Sdf>(self, other: B) -> Subtraction<Self, B>
+    where
+        Self: Sized,
+    {
+        Subtraction(self, other)
+    }
+
+    fn intersect<B: Sdf>(self, other: B) -> Intersection<Self, B>
+    where
+        Self: Sized,
+    {
+        Intersection(self, other)
+    }
+
+    // объединение с плавным переходом шириной radius
+    fn smooth_union<B: Sdf>(self, other: B, radius: f64) -> SmoothUnion<Self, B>
+    where
+        Self: Sized,
+    {
+        SmoothUnion {
+            first: self,
+            second: other,
+            radius,
+        }
+    }
+
+    fn translate(self, offset: [f64; 3]) -> Translate<Self>
+    where
+        Self: Sized,
+    {
+        Translate {
+            shape: self,
+            offset,
+        }
+    }
+}
+
+impl<S: Sdf + ?Sized> Sdf for Box<S> {
+    fn distance(&self, position: [f64; 3]) -> f64 {
+        self.as_ref().distance(position)
+    }
+}
+
+impl<S: Sdf + ?Sized> Sdf for Arc<S> {
+    fn distance(&self, position: [f64; 3]) -> f64 {
+        self.as_ref().distance(position)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Sphere {
+    pub center: [f64; 3],
+    pub radius: f64,
+}
+
+impl Sdf for Sphere {
+    fn distance(&self, position: [f64; 3]) -> f64 {
+        (DVec3::from(position) - DVec3::from(self.center)).length() - self.radius
+    }
+}
+
+/// Прямоугольный параллелепипед, выровненный по осям.
+#[derive(Clone, Debug)]
+pub struct Cuboid {
+    pub center: [f64; 3],
+    pub half_extents: [f64; 3],
+}
+
+impl Sdf for Cuboid {
+    fn distance(&self, position: [f64; 3]) -> f64 {
+        let q = (DVec3::from(position) - DVec3::from(self.center)).abs()
+            - DVec3::from(self.half_extents);
+        q.max(DVec3::ZERO).length() + q.max_element().min(0.)
+    }
+}
+
+/// Отрезок от start до end, утолщенный до radius.
+#[derive(Clone, Debug)]
+pub struct Capsule {
+    pub start: [f64; 3],
+    pub end: [f64; 3],
+    pub radius: f64,
+}
+
+impl Sdf for Capsule {
+    fn distance(&self, position: [f64; 3]) -> f64 {
+        let from_start = DVec3::from(position) - DVec3::from(self.start);
+        let axis = DVec3::from(self.end) - DVec3::from(self.start);
+        let t = (from_start.dot(axis) / axis.length_squared().max(f64::EPSILON)).clamp(0., 1.);
+        (from_start - axis * t).length() - self.radius
+    }
+}
+
+/// Вертикальный цилиндр с плоскими торцами.
+#[derive(Clone, Debug)]
+pub struct Cylinder {
+    pub center: [f64; 3],
+    pub radius: f64,
+    pub half_height: f64,
+}
+
+impl Sdf for Cylinder {
+    fn distance(&self, position: [f64; 3]) -> f64 {
+        let local = DVec3::from(position) - DVec3::from(self.center);
+        let q = DVec2::new(
+            DVec2::new(local.x, local.z).length() - self.radius,
+            local.y.abs() - self.half_height,
+        );
+        q.max(DVec2::ZERO).length() + q.max_element().min(0.)
+    }
+}
+
+/// Тор, лежащий в горизонтальной плоскости.
+#[derive(Clone, Debug)]
+pub struct Torus {
+    pub center: [f64; 3],
+    // радиус окружности, по которой идет центр трубки
+    pub major_radius: f64,
+    // радиус трубки
+    pub minor_radius: f64,
+}
+
+impl Sdf for Torus {
+    fn distance(&self, position: [f64; 3]) -> f64 {
+        let local = DVec3::from(position) - DVec3::from(self.center);
+        DVec2::new(
+            DVec2::new(local.x, local.z).length() - self.major_radius,
+            local.y,
+        )
+        .length()
+            - self.minor_radius
+    }
+}
+
+/// Полупространство: внутри все точки ниже плоскости по направлению normal.
+#[derive(Clone, Debug)]
+pub struct Plane {
+    // единичная нормаль
+    normal: DVec3,
+    // расстояние от начала координат до плоскости вдоль normal
+    offset: f64,
+}
+
+impl Plane {
+    // normal нормализуется; нулевая нормаль не задает плоскость
+    pub fn new(normal: [f64; 3], offset: f64) -> Self {
+        let normal = DVec3::from(normal)
+            .try_normalize()
+            .expect("Plane needs a finite non-zero normal");
+        Plane { normal, offset }
+    }
+
+    pub fn normal(&self) -> [f64; 3] {
+        self.normal.to_array()
+    }
+
+    pub fn offset(&self) -> f64 {
+        self.offset
+    }
+}
+
+impl Sdf for Plane {
+    fn distance(&self, position: [f64; 3]) -> f64 {
+        DVec3::from(position).dot(self.normal) - self.offset
+    }
+}
+
+pub struct Union<A, B>(pub A, pub B);
+
+impl<A: Sdf, B: Sdf> Sdf for Union<A, B> {
+    fn distance(&self, position: [f64; 3]) -> f64 {
+        CsgOperation::Union.apply(self.0.distance(position), self.1.distance(position))
+    }
+}
+
+pub struct Subtraction<A, B>(pub A, pub B);
+
+impl<A: Sdf, B: Sdf> Sdf for Subtraction<A, B> {
+    fn distance(&self, position: [f64; 3]) -> f64 {
+        CsgOperation::Subtraction.apply(self.0.distance(position), self.1.distance(position))
+    }
+}
+
+pub struct Intersection<A, B>(pub A, pub B);
+
+impl<A: Sdf, B: Sdf> Sdf for Intersection<A, B> {
+    fn distance(&self, position: [f64; 3]) -> f64 {
+        CsgOperation::Intersection.apply(self.0.distance(position), self.1.distance(position))
+    }
+}
+
+pub struct SmoothUnion<A, B> {
+    pub first: A,
+    pub second: B,
+    pub radius: f64,
+}
+
+impl<A: Sdf, B: Sdf> Sdf for SmoothUnion<A, B> {
+    fn distance(&self, position: [f64; 3]) -> f64 {
+        CsgOperation::SmoothUnion(self.radius).apply(
+            self.first.distance(position),
+            self.second.distance(position),
+        )
+    }
+}
+
+pub struct Translate<S> {
+    pub shape: S,
+    pub offset: [f64; 3],
+}
+
+impl<S: Sdf> Sdf for Translate<S> {
+    fn distance(&self, position: [f64; 3]) -> f64 {
+        self.shape
+            .distance((DVec3::from(position) - DVec3::from(self.offset)).to_array())
+    }
+}
+
+/// Операция конструктивной геометрии над двумя расстояниями.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CsgOperation {
+    Union,
+    // из первой фигуры вырезается вторая
+    Subtraction,
+    Intersection,
+    // объединение с плавным переходом заданной ширины
+    SmoothUnion(f64),
+}
+
+impl CsgOperation {
+    pub fn apply(&self, first: f64, second: f64) -> f64 {
+        match *self {
+            CsgOperation::Union => first.min(second),
+            CsgOperation::Subtraction => first.max(-second),
+            CsgOperation::Intersection => first.max(second),
+            CsgOperation::SmoothUnion(radius) => {
+                if radius <= 0. {
+                    return first.min(second);
+                }
+                let h = (0.5 + 0.5 * (second - first) / radius).clamp(0., 1.);
+                second + (first - second) * h - radius * h * (1. - h)
+            }
+        }
+    }
+
+    // добавляет ли операция материал второй фигуры
+    fn adds_material(&self) -> bool {
+        matches!(self, CsgOperation::Union | CsgOperation::SmoothUnion(_))
+    }
+}
+
+/// Фигура как самостоятельная функция плотности.
+pub struct SdfDensity<S> {
+    shape: S,
+    material: VoxelMaterial,
+}
+
+impl<S: Sdf> SdfDensity<S> {
+    pub fn new(shape: S, material: VoxelMaterial) -> Self {
+        SdfDensity { shape, material }
+    }
+}
+
+impl<S: Sdf> DensityFunction for SdfDensity<S> {
+    fn sample(&self, position: [f64; 3]) -> f64 {
+        -self.shape.distance(position)
+    }
+
+    fn material(&self, _position: [f64; 3]) -> VoxelMaterial {
+        self.material
+    }
+}
+
+/// Рельеф с вставленной в него фигурой: скалой, аркой, выемкой под постройку.
+///
+/// Плотность рельефа считается приближенным расстоянием до его поверхности,
+/// поэтому операции применяются к ней так же, как к двум фигурам.
+pub struct SdfTerrain {
+    terrain: Arc<dyn DensityFunction>,
+    shape: Arc<dyn Sdf>,
+    operation: CsgOperation,
+    material: VoxelMaterial,
+}
+
+impl SdfTerrain {
+    pub fn new(
+        terrain: Arc<dyn DensityFunction>,
+        shape: impl Sdf + 'static,
+        operation: CsgOperation,
+        material: VoxelMaterial,
+    ) -> Self {
+        SdfTerrain {
+            terrain,
+            shape: Arc::new(shape),
+            operation,
+            material,
+        }
+    }
+}
+
+impl DensityFunction for SdfTerrain {
+    fn sample(&self, position: [f64; 3]) -> f64 {
+        -self.operation.apply(
+            -self.terrain.sample(position),
+            self.shape.distance(position),
+        )
+    }
+
+    fn sample_column(&self, column: [f64; 2], heights: &[f64], densities: &mut [f64]) {
+        self.terrain.sample_column(column, heights, densities);
+        for (height, density) in heights.iter().zip(densities.iter_mut()) {
+            let distance = self.shape.distance([column[0], *height, column[1]]);
+            *density = -self.operation.apply(-*density, distance);
+        }
+    }
+
+    fn material(&self, position: [f64; 3]) -> VoxelMaterial {
+        if self.operation.adds_material() && self.shape.distance(position) <= MATERIAL_MARGIN {
+            self.material
+        } else {
+            self.terrain.material(position)
+        }
+    }
+
+    fn surface_height(&self, position: [f64; 2]) -> Option<f64> {
+        self.terrain.surface_height(position)
+    }
+
+    fn water_level(&self, position: [f64; 2]) -> Option<f64> {
+        self.terrain.water_level(position)
+    }
+
+    fn depth(&self, position: [f64; 3]) -> Option<f64> {
+        self.terrain.depth(position)
+    }
+
+    fn up(&self, position: [f64; 3]) -> [f64; 3] {
+        self.terrain.up(position)
+    }
+}
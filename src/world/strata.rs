@@ -0,0 +1,212 @@
+use std::sync::Arc;
+
+use noise::{Fbm, MultiFractal, NoiseFn, SuperSimplex};
+
+use super::density::DensityFunction;
+use super::material::VoxelMaterial;
+use super::world_config::WorldSeed;
+
+// шум рудных тел распределен примерно нормально с таким отклонением
+const ORE_NOISE_DEVIATION: f64 = 0.52;
+
+/// Слой породы заданной толщины. Слои идут сверху вниз, последний не имеет дна.
+#[derive(Clone, Debug)]
+pub struct StratumSettings {
+    pub material: VoxelMaterial,
+    pub thickness: f64,
+}
+
+/// Рудное тело: скопления материала в заданном диапазоне глубин.
+#[derive(Clone, Debug)]
+pub struct OreSettings {
+    pub material: VoxelMaterial,
+    // примерная доля объема диапазона глубин, занятая рудой (0..1)
+    pub rarity: f64,
+    pub min_depth: f64,
+    pub max_depth: f64,
+    // характерный размер одного скопления в метрах
+    pub cluster_size: f64,
+}
+
+/// Материалы под поверхностью. Расстояния в метрах.
+#[derive(Clone, Debug)]
+pub struct SubsurfaceSettings {
+    // толщина верхнего слоя, где остается материал поверхности (трава, песок, снег)
+    pub surface_thickness: f64,
+    pub strata: Vec<StratumSettings>,
+    // насколько неровны границы слоев
+    pub strata_undulation: f64,
+    // при пересечении рудных тел побеждает руда, стоящая раньше в списке
+    pub ores: Vec<OreSettings>,
+}
+
+impl Default for SubsurfaceSettings {
+    fn default() -> Self {
+        SubsurfaceSettings {
+            surface_thickness: 1.5,
+            strata: vec![
+                StratumSettings {
+                    material: VoxelMaterial::Dirt,
+                    thickness: 4.,
+                },
+                StratumSettings {
+                    material: VoxelMaterial::Rock,
+                    thickness: 80.,
+                },
+                StratumSettings {
+                    material: VoxelMaterial::Basalt,
+                    thickness: f64::INFINITY,
+                },
+            ],
+            strata_undulation: 3.,
+            ores: vec![
+                OreSettings {
+                    material: VoxelMaterial::Gold,
+                    rarity: 0.02,
+                    min_depth: 60.,
+                    max_depth: 400.,
+                    cluster_size: 3.,
+                },
+                OreSettings {
+                    material: VoxelMaterial::Iron,
+                    rarity: 0.04,
+                    min_depth: 16.,
+                    max_depth: 200.,
+                    cluster_size: 4.,
+                },
+                OreSettings {
+                    material: VoxelMaterial::Coal,
+                    rarity: 0.06,
+                    min_depth: 6.,
+                    max_depth: 100.,
+                    cluster_size: 6.,
+                },
+            ],
+        }
+    }
+}
+
+/// Пласты породы и рудные тела внутри рельефа.
+///
+/// Форма рельефа не меняется: слой только отвечает на `DensityFunction::material`,
+/// поэтому материалы видны на стенах пещер и в раскопках, а запросы копания
+/// и лучей читают их из той же функции плотности. Глубина отсчитывается от
+/// `DensityFunction::depth` рельефа; без нее материал берется из исходного рельефа.
+pub struct SubsurfaceMaterials {
+    terrain: Arc<dyn DensityFunction>,
+    strata_noise: SuperSimplex,
+    ore_noise: Vec<Fbm<SuperSimplex>>,
+    settings: SubsurfaceSettings,
+}
+
+impl SubsurfaceMaterials {
+    pub fn new(
+        terrain: Arc<dyn DensityFunction>,
+        seed: WorldSeed,
+        settings: SubsurfaceSettings,
+    ) -> Self {
+        SubsurfaceMaterials {
+            terrain,
+            strata_noise: SuperSimplex::new(seed.sub_seed("strata").noise_seed()),
+            // у каждой руды свой шум, чтобы рудные тела не совпадали
+            ore_noise: (0..settings.ores.len())
+                .map(|index| {
+                    Fbm::<SuperSimplex>::new(seed.sub_seed(&format!("ore_{index}")).noise_seed())
+                        .set_octaves(2)
+                })
+                .collect(),
+            settings,
+        }
+    }
+
+    pub fn settings(&self) -> &SubsurfaceSettings {
+        &self.settings
+    }
+
+    fn ore_at(&self, position: [f64; 3], depth: f64) -> Option<VoxelMaterial> {
+        self.settings
+            .ores
+            .iter()
+            .zip(&self.ore_noise)
+            .find(|(ore, noise)| {
+                if depth < ore.min_depth || depth > ore.max_depth {
+                    return false;
+                }
+                let point = position.map(|value| value / ore.cluster_size);
+                noise.get(point) > ore_threshold(ore.rarity)
+            })
+            .map(|(ore, _)| ore.material)
+    }
+
+    fn stratum_at(&self, position: [f64; 3], depth: f64) -> VoxelMaterial {
+        let wobble =
+            self.strata_noise
+                .get([position[0] / 64., position[1] / 64., position[2] / 64.])
+                * self.settings.strata_undulation;
+        let mut top = 0.;
+        for stratum in &self.settings.strata {
+            top += stratum.thickness;
+            if depth + wobble < top {
+                return stratum.material;
+            }
+        }
+        self.settings
+            .strata
+            .last()
+            .map(|stratum| stratum.material)
+            .unwrap_or_default()
+    }
+
+    // материал на глубине depth под поверхностью
+    pub fn subsurface_material(&self, position: [f64; 3], depth: f64) -> VoxelMaterial {
+        self.ore_at(position, depth)
+            .unwrap_or_else(|| self.stratum_at(position, depth))
+    }
+}
+
+impl DensityFunction for SubsurfaceMaterials {
+    fn sample(&self, position: [f64; 3]) -> f64 {
+        self.terrain.sample(position)
+    }
+
+    fn sample_column(&self, column: [f64; 2], heights: &[f64], densities: &mut [f64]) {
+        self.terrain.sample_column(column, heights, densities);
+    }
+
+    fn material(&self, position: [f64; 3]) -> VoxelMaterial {
+        let Some(depth) = self.terrain.depth(position) else {
+            return self.terrain.material(position);
+        };
+        if depth <= self.settings.surface_thickness {
+            return self.terrain.material(position);
+        }
+        self.subsurface_material(position, depth)
+    }
+
+    fn surface_height(&self, position: [f64; 2]) -> Option<f64> {
+        self.terrain.surface_height(position)
+    }
+
+    fn water_level(&self, position: [f64; 2]) -> Option<f64> {
+        self.terrain.water_level(position)
+    }
+
+    fn depth(&self, position: [f64; 3]) -> Option<f64> {
+        self.terrain.depth(position)
+    }
+
+    fn up(&self, position: [f64; 3]) -> [f64; 3] {
+        self.terrain.up(position)
+    }
+}
+
+// порог шума, выше которого оказывается доля rarity всех точек
+fn ore_threshold(rarity: f64) -> f64 {
+    let tail = rarity.clamp(1e-6, 0.5);
+    // обратная функция нормального распределения (Абрамовиц и Стиган, 26.2.23)
+    let t = (-2. * tail.ln()).sqrt();
+    let quantile = t
+        - (2.515517 + 0.802853 * t + 0.010328 * t * t)
+            / (1. + 1.432788 * t + 0.189269 * t * t + 0.001308 * t * t * t);
+    quantile * ORE_NOISE_DEVIATION
+}
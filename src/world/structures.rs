@@ -0,0 +1,520 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use bevy::math::{DQuat, DVec3};
+use bevy::prelude::*;
+
+use super::biome::{Biome, BiomeMap};
+use super::chunk_from_marching_cubes::VoxelChunk;
+use super::density::{DensityFunction, WorldDensity};
+use super::material::VoxelMaterial;
+use super::random::{self, Random};
+use super::sdf::{CsgOperation, Sdf};
+use super::tiles::TileCache;
+use super::world_config::WorldSeed;
+
+// размер области, для которой расстановка считается и кэшируется целиком
+const REGION_SIZE: f64 = 256.;
+const REGION_CACHE_CAPACITY: usize = 256;
+// сколько последних областей каждый поток держит у себя, не блокируя общий кэш
+const RECENT_REGIONS: usize = 16;
+// на сколько метров под основанием и над ним проверяются правки игрока
+const PROP_PROBE: f64 = 0.5;
+
+static NEXT_PLACEMENT_ID: AtomicU64 = AtomicU64::new(0);
+
+// область, прочитанная потоком: ключ - (расстановка, область)
+type RecentRegion = ((u64, [i64; 2]), Arc<Vec<Placement>>);
+
+thread_local! {
+    static RECENT: RefCell<Vec<RecentRegion>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Что ставится в точке размещения.
+#[derive(Clone)]
+pub enum StructureKind {
+    /// Фигура впечатывается в плотность. Она задается в локальных координатах:
+    /// начало - точка на поверхности, ось y направлена вверх.
+    Stamp {
+        shape: Arc<dyn Sdf>,
+        operation: CsgOperation,
+        material: VoxelMaterial,
+    },
+    /// Сущность `StructureProp` появляется дочерней к чанку, в котором стоит.
+    Prop,
+}
+
+impl fmt::Debug for StructureKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StructureKind::Stamp { operation, .. } => write!(f, "Stamp({operation:?})"),
+            StructureKind::Prop => write!(f, "Prop"),
+        }
+    }
+}
+
+/// Правила размещения одного вида построек. Расстояния в метрах.
+#[derive(Clone, Debug)]
+pub struct StructureSettings {
+    pub name: String,
+    pub kind: StructureKind,
+    // наименьшее расстояние между постройками этого вида
+    pub spacing: f64,
+    // доля возможных мест, где постройка действительно появляется (0..1)
+    pub chance: f64,
+    // наибольший уклон поверхности (подъем на метр)
+    pub max_slope: f64,
+    // биомы, в которых разрешена постройка; пустой список - любые
+    pub biomes: Vec<Biome>,
+    pub avoid_water: bool,
+    // радиус, за пределами которого фигура точно не влияет на плотность
+    pub radius: f64,
+}
+
+/// Размещенная постройка.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Placement {
+    // индекс в списке настроек построек
+    pub structure: usize,
+    pub position: [f64; 3],
+    // нормаль поверхности в точке постройки
+    pub normal: [f64; 3],
+    // поворот вокруг нормали в радианах
+    pub yaw: f64,
+}
+
+/// Детерминированная расстановка построек по зерну мира.
+///
+/// Для каждого вида построек плоскость делится на ячейки со стороной
+/// `spacing / sqrt(2)`, в каждой ячейке есть кандидат со случайным приоритетом,
+/// и кандидат остается, только если ближе `spacing` нет кандидата с большим
+/// приоритетом (Poisson-disk без последовательного перебора). Результат для точки
+/// зависит только от зерна и ее окрестности, поэтому не меняется от порядка
+/// загрузки чанков, а постройка на границе чанков одинакова с обеих сторон.
+pub struct StructurePlacement {
+    terrain: Arc<dyn DensityFunction>,
+    biome_map: BiomeMap,
+    seed: WorldSeed,
+    structures: Vec<StructureSettings>,
+    regions: TileCache<Vec<Placement>>,
+    // отличает экземпляры в кэшах потоков
+    id: u64,
+}
+
+impl StructurePlacement {
+    pub fn new(
+        terrain: Arc<dyn DensityFunction>,
+        seed: WorldSeed,
+        biome_size: f64,
+        structures: Vec<StructureSettings>,
+    ) -> Self {
+        StructurePlacement {
+            terrain,
+            biome_map: BiomeMap::new(seed, biome_size),
+            seed: seed.sub_seed("structures"),
+            structures,
+            regions: TileCache::new(REGION_CACHE_CAPACITY),
+            id: NEXT_PLACEMENT_ID.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    pub fn structures(&self) -> &[StructureSettings] {
+        &self.structures
+    }
+
+    fn cell_size(&self, structure: usize) -> f64 {
+        self.structures[structure].spacing.max(f64::EPSILON) / std::f64::consts::SQRT_2
+    }
+
+    // кандидат ячейки: положение (x, z), приоритет и поворот; None, если ячейка пуста
+    fn candidate(&self, structure: usize, cell: [i64; 2]) -> Option<([f64; 2], f64, f64)> {
+        let settings = &self.structures[structure];
+        let mut random = Random::new(random::hash_coordinates(
+            self.seed.value() ^ random::mix(structure as u64),
+            &cell,
+        ));
+        let cell_size = self.cell_size(structure);
+        let position = [
+            (cell[0] as f64 + random.next_f64()) * cell_size,
+            (cell[1] as f64 + random.next_f64()) * cell_size,
+        ];
+        let priority = random.next_f64();
+        let yaw = random.range(0., std::f64::consts::TAU);
+        (random.next_f64() < settings.chance).then_some((position, priority, yaw))
+    }
+
+    // кандидат побеждает всех соседей ближе spacing
+    fn wins_neighbourhood(
+        &self,
+        structure: usize,
+        cell: [i64; 2],
+        position: [f64; 2],
+        priority: f64,
+    ) -> bool {
+        let spacing = self.structures[structure].spacing;
+        let reach = (spacing / self.cell_size(structure)).ceil() as i64;
+        for z in -reach..=reach {
+            for x in -reach..=reach {
+                if x == 0 && z == 0 {
+                    continue;
+                }
+                let neighbour = [cell[0] + x, cell[1] + z];
+                let Some((other, other_priority, _)) = self.candidate(structure, neighbour) else {
+                    continue;
+                };
+                let distance = (other[0] - position[0]).hypot(other[1] - position[1]);
+                if distance < spacing && (other_priority, neighbour) > (priority, cell) {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    // правила местности: уклон, биом и вода
+    // высота и нормаль поверхности, если постройка может здесь стоять
+    fn fits_terrain(&self, structure: usize, position: [f64; 2]) -> Option<(f64, [f64; 3])> {
+        let settings = &self.structures[structure];
+        let height = self.terrain.surface_height(position)?;
+        let step = 1.;
+        let slope_x = self
+            .terrain
+            .surface_height([position[0] + step, position[1]])?
+            - self
+                .terrain
+                .surface_height([position[0] - step, position[1]])?;
+        let slope_z = self
+            .terrain
+            .surface_height([position[0], position[1] + step])?
+            - self
+                .terrain
+                .surface_height([position[0], position[1] - step])?;
+        if slope_x.hypot(slope_z) / (2. * step) > settings.max_slope {
+            return None;
+        }
+        let normal = DVec3::new(-slope_x / (2. * step), 1., -slope_z / (2. * step)).normalize();
+        if !settings.biomes.is_empty()
+            && !settings.biomes.contains(&self.biome_map.biome_at(position))
+        {
+            return None;
+        }
+        if settings.avoid_water
+            && self
+                .terrain
+                .water_level(position)
+                .is_some_and(|level| level > height)
+        {
+            return None;
+        }
+        Some((height, normal.to_array()))
+    }
+
+    fn compute_region(&self, region: [i64; 2]) -> Vec<Placement> {
+        let min = [
+            region[0] as f64 * REGION_SIZE,
+            region[1] as f64 * REGION_SIZE,
+        ];
+        let mut placements = Vec::new();
+        for structure in 0..self.structures.len() {
+            let cell_size = self.cell_size(structure);
+            let first = [
+                (min[0] / cell_size).floor() as i64,
+                (min[1] / cell_size).floor() as i64,
+            ];
+            let last = [
+                ((min[0] + REGION_SIZE) / cell_size).floor() as i64,
+                ((min[1] + REGION_SIZE) / cell_size).floor() as i64,
+            ];
+            for z in first[1]..=last[1] {
+                for x in first[0]..=last[0] {
+                    let cell = [x, z];
+                    let Some((position, priority, yaw)) = self.candidate(structure, cell) else {
+                        continue;
+                    };
+                    // каждая постройка принадлежит ровно одной области
+                    if position[0] < min[0]
+                        || position[1] < min[1]
+                        || position[0] >= min[0] + REGION_SIZE
+                        || position[1] >= min[1] + REGION_SIZE
+                    {
+                        continue;
+                    }
+                    if !self.wins_neighbourhood(structure, cell, position, priority) {
+                        continue;
+                    }
+                    let Some((height, normal)) = self.fits_terrain(structure, position) else {
+                        continue;
+                    };
+                    placements.push(Placement {
+                        structure,
+                        position: [position[0], height, position[1]],
+                        normal,
+                        yaw,
+                    });
+                }
+            }
+        }
+        placements
+    }
+
+    /// Все постройки, стоящие в прямоугольнике XZ [min, max).
+    pub fn placements_in(&self, min: [f64; 2], max: [f64; 2]) -> Vec<Placement> {
+        let first = [
+            (min[0] / REGION_SIZE).floor() as i64,
+            (min[1] / REGION_SIZE).floor() as i64,
+        ];
+        let last = [
+            (max[0] / REGION_SIZE).floor() as i64,
+            (max[1] / REGION_SIZE).floor() as i64,
+        ];
+        let mut placements = Vec::new();
+        for z in first[1]..=last[1] {
+            for x in first[0]..=last[0] {
+                let region = self.region([x, z]);
+                placements.extend(
+                    region
+                        .iter()
+                        .filter(|placement| {
+                            (min[0]..max[0]).contains(&placement.position[0])
+                                && (min[1]..max[1]).contains(&placement.position[2])
+                        })
+                        .cloned(),
+                );
+            }
+        }
+        placements
+    }
+
+    // готовая область: сначала из кэша потока, затем из общего
+    fn region(&self, region: [i64; 2]) -> Arc<Vec<Placement>> {
+        let key = (self.id, region);
+        RECENT.with(|recent| {
+            let mut recent = recent.borrow_mut();
+            if let Some((_, placements)) = recent.iter().find(|(other, _)| *other == key) {
+                return placements.clone();
+            }
+            let placements = self
+                .regions
+                .get_or_insert_with(region, || self.compute_region(region));
+            if recent.len() >= RECENT_REGIONS {
+                recent.remove(0);
+            }
+            recent.push((key, placements.clone()));
+            placements
+        })
+    }
+
+    // точка в локальных координатах постройки
+    fn to_local(placement: &Placement, position: [f64; 3]) -> [f64; 3] {
+        let offset = DVec3::from(position) - DVec3::from(placement.position);
+        (DQuat::from_rotation_y(-placement.yaw) * offset).to_array()
+    }
+
+    // штампы, чьи окна по x и z задевают столбец column
+    fn stamps_around(&self, column: [f64; 2]) -> Vec<Placement> {
+        let reach = self
+            .structures
+            .iter()
+            .filter(|settings| matches!(settings.kind, StructureKind::Stamp { .. }))
+            .map(|settings| settings.radius)
+            .fold(0., f64::max);
+        if reach <= 0. {
+            return Vec::new();
+        }
+        self.placements_in(
+            [column[0] - reach, column[1] - reach],
+            [column[0] + reach, column[1] + reach],
+        )
+        .into_iter()
+        .filter(|placement| {
+            matches!(
+                self.structures[placement.structure].kind,
+                StructureKind::Stamp { .. }
+            )
+        })
+        .collect()
+    }
+
+    fn stamp_reaches(&self, placement: &Placement, position: [f64; 3]) -> bool {
+        DVec3::from(position).distance(DVec3::from(placement.position))
+            <= self.structures[placement.structure].radius
+    }
+
+    fn stamps_near(&self, position: [f64; 3]) -> Vec<Placement> {
+        self.stamps_around([position[0], position[2]])
+            .into_iter()
+            .filter(|placement| self.stamp_reaches(placement, position))
+            .collect()
+    }
+}
+
+/// Рельеф с впечатанными постройками из `StructurePlacement`.
+pub struct StructureTerrain {
+    terrain: Arc<dyn DensityFunction>,
+    placement: Arc<StructurePlacement>,
+}
+
+impl StructureTerrain {
+    pub fn new(terrain: Arc<dyn DensityFunction>, placement: Arc<StructurePlacement>) -> Self {
+        StructureTerrain { terrain, placement }
+    }
+
+    pub fn placement(&self) -> &Arc<StructurePlacement> {
+        &self.placement
+    }
+
+    // плотность после впечатывания одного штампа
+    fn stamp(&self, placement: &Placement, position: [f64; 3], density: f64) -> f64 {
+        match &self.placement.structures[placement.structure].kind {
+            StructureKind::Stamp {
+                shape, operation, ..
+            } => {
+                let distance = shape.distance(StructurePlacement::to_local(placement, position));
+                -operation.apply(-density, distance)
+            }
+            StructureKind::Prop => density,
+        }
+    }
+}
+
+impl DensityFunction for StructureTerrain {
+    fn sample(&self, position: [f64; 3]) -> f64 {
+        let mut density = self.terrain.sample(position);
+        for placement in self.placement.stamps_near(position) {
+            density = self.stamp(&placement, position, density);
+        }
+        density
+    }
+
+    fn sample_column(&self, column: [f64; 2], heights: &[f64], densities: &mut [f64]) {
+        self.terrain.sample_column(column, heights, densities);
+        let stamps = self.placement.stamps_around(column);
+        if stamps.is_empty() {
+            return;
+        }
+        for (height, density) in heights.iter().zip(densities.iter_mut()) {
+            let position = [column[0], *height, column[1]];
+            for placement in &stamps {
+                if self.placement.stamp_reaches(placement, position) {
+                    *density = self.stamp(placement, position, *density);
+                }
+            }
+        }
+    }
+
+    fn material(&self, position: [f64; 3]) -> VoxelMaterial {
+        for placement in self.placement.stamps_near(position) {
+            if let StructureKind::Stamp {
+                shape,
+                operation: CsgOperation::Union | CsgOperation::SmoothUnion(_),
+                material,
+            } = &self.placement.structures[placement.structure].kind
+            {
+                if shape.distance(StructurePlacement::to_local(&placement, position)) <= 1. {
+                    return *material;
+                }
+            }
+        }
+        self.terrain.material(position)
+    }
+
+    fn surface_height(&self, position: [f64; 2]) -> Option<f64> {
+        self.terrain.surface_height(position)
+    }
+
+    fn water_level(&self, position: [f64; 2]) -> Option<f64> {
+        self.terrain.water_level(position)
+    }
+
+    fn depth(&self, position: [f64; 3]) -> Option<f64> {
+        self.terrain.depth(position)
+    }
+
+    fn up(&self, position: [f64; 3]) -> [f64; 3] {
+        self.terrain.up(position)
+    }
+}
+
+/// Постройка-объект, поставленная генератором. Появляется дочерней к чанку,
+/// в котором стоит, и удаляется вместе с его мешем; после правок чанка
+/// пересоздается, если земля под ней не изменилась. Внешний вид добавляют
+/// системы игры по `Added<StructureProp>`.
+#[derive(Component, Clone, Debug)]
+pub struct StructureProp {
+    pub name: String,
+    pub placement: Placement,
+}
+
+pub(crate) fn chunk_props(
+    placement: &StructurePlacement,
+    voxel_chunk: &VoxelChunk,
+) -> Vec<StructureProp> {
+    let origin = voxel_chunk.world_origin().map(|value| value as f64);
+    let size = VoxelChunk::size_chunk() as f64;
+    placement
+        .placements_in([origin[0], origin[2]], [origin[0] + size, origin[2] + size])
+        .into_iter()
+        .filter(|prop| {
+            matches!(
+                placement.structures[prop.structure].kind,
+                StructureKind::Prop
+            ) && (origin[1]..origin[1] + size).contains(&prop.position[1])
+        })
+        .map(|prop| StructureProp {
+            name: placement.structures[prop.structure].name.clone(),
+            placement: prop,
+        })
+        .collect()
+}
+
+// `edited` - плотность с правками игрока: постройка, у которой выкопали
+// землю под основанием или засыпали его, не ставится
+pub(crate) fn spawn_props(
+    commands: &mut Commands,
+    world_density: &WorldDensity,
+    edited: &dyn DensityFunction,
+    entity: Entity,
+    voxel_chunk: &VoxelChunk,
+) {
+    let Some(placement) = world_density.structures() else {
+        return;
+    };
+    let origin = voxel_chunk.world_origin().map(|value| value as f64);
+    for prop in chunk_props(placement, voxel_chunk) {
+        if !ground_unchanged(world_density.function(), edited, &prop.placement) {
+            continue;
+        }
+        let translation = Vec3::new(
+            (prop.placement.position[0] - origin[0]) as f32,
+            (prop.placement.position[1] - origin[1]) as f32,
+            (prop.placement.position[2] - origin[2]) as f32,
+        );
+        // постройка стоит по нормали поверхности и повернута вокруг нее
+        let normal = prop.placement.normal.map(|value| value as f32);
+        let rotation = Quat::from_rotation_arc(Vec3::Y, Vec3::from(normal))
+            * Quat::from_rotation_y(prop.placement.yaw as f32);
+        commands.entity(entity).with_children(|parent| {
+            parent.spawn((
+                SpatialBundle::from_transform(
+                    Transform::from_translation(translation).with_rotation(rotation),
+                ),
+                prop,
+            ));
+        });
+    }
+}
+
+// земля под основанием и воздух над ним те же, что у процедурного рельефа
+fn ground_unchanged(
+    density: &dyn DensityFunction,
+    edited: &dyn DensityFunction,
+    placement: &Placement,
+) -> bool {
+    [-PROP_PROBE, PROP_PROBE].into_iter().all(|offset| {
+        let probe =
+            [0, 1, 2].map(|axis| placement.position[axis] + placement.normal[axis] * offset);
+        (density.sample(probe) > 0.) == (edited.sample(probe) > 0.)
+    })
+}
@@ -0,0 +1,180 @@
+use std::sync::Arc;
+
+use noise::{Fbm, MultiFractal, NoiseFn, SuperSimplex};
+
+use super::caves::smoothstep;
+use super::density::DensityFunction;
+use super::material::VoxelMaterial;
+use super::world_config::WorldSeed;
+
+/// Искажение координат перед выборкой рельефа. Расстояния в метрах.
+#[derive(Clone, Debug)]
+pub struct DomainWarpSettings {
+    // наибольшее смещение точки выборки
+    pub strength: f64,
+    // масштаб шума искажения
+    pub scale: f64,
+}
+
+impl Default for DomainWarpSettings {
+    fn default() -> Self {
+        DomainWarpSettings {
+            strength: 24.,
+            scale: 128.,
+        }
+    }
+}
+
+/// Объемный шум около поверхности, из которого получаются навесы и арки.
+#[derive(Clone, Debug)]
+pub struct OverhangSettings {
+    // вклад шума в плотность на самой поверхности
+    pub amplitude: f64,
+    pub scale: f64,
+    // на каком расстоянии от поверхности по вертикали шум затухает
+    pub band: f64,
+}
+
+impl Default for OverhangSettings {
+    fn default() -> Self {
+        OverhangSettings {
+            amplitude: 16.,
+            scale: 40.,
+            band: 32.,
+        }
+    }
+}
+
+/// Парящие острова в слое на заданной высоте.
+#[derive(Clone, Debug)]
+pub struct FloatingIslandSettings {
+    // высота середины слоя островов
+    pub altitude: f64,
+    // половина толщины слоя
+    pub thickness: f64,
+    pub scale: f64,
+    // порог шума: чем ближе к 1, тем реже острова
+    pub threshold: f64,
+}
+
+impl Default for FloatingIslandSettings {
+    fn default() -> Self {
+        FloatingIslandSettings {
+            altitude: 120.,
+            thickness: 24.,
+            scale: 96.,
+            threshold: 0.3,
+        }
+    }
+}
+
+/// Набор объемных особенностей рельефа. Каждая включается отдельно.
+#[derive(Clone, Debug, Default)]
+pub struct TerrainFeatures {
+    pub domain_warp: Option<DomainWarpSettings>,
+    pub overhangs: Option<OverhangSettings>,
+    pub floating_islands: Option<FloatingIslandSettings>,
+}
+
+impl TerrainFeatures {
+    pub fn is_empty(&self) -> bool {
+        self.domain_warp.is_none() && self.overhangs.is_none() && self.floating_islands.is_none()
+    }
+}
+
+/// Добавляет к рельефу искажение координат, навесы и парящие острова.
+///
+/// `surface_height` возвращает высоту исходного рельефа без искажений,
+/// поэтому для слоев выше по конвейеру (например, пещер) она приблизительна.
+pub struct FeatureTerrain {
+    terrain: Arc<dyn DensityFunction>,
+    warp: [Fbm<SuperSimplex>; 3],
+    overhangs: Fbm<SuperSimplex>,
+    islands: Fbm<SuperSimplex>,
+    features: TerrainFeatures,
+}
+
+impl FeatureTerrain {
+    pub fn new(
+        terrain: Arc<dyn DensityFunction>,
+        seed: WorldSeed,
+        features: TerrainFeatures,
+    ) -> Self {
+        let fbm = |layer: &str, octaves: usize| {
+            Fbm::<SuperSimplex>::new(seed.sub_seed(layer).noise_seed()).set_octaves(octaves)
+        };
+        FeatureTerrain {
+            terrain,
+            warp: [fbm("warp_x", 3), fbm("warp_y", 3), fbm("warp_z", 3)],
+            overhangs: fbm("overhangs", 3),
+            islands: fbm("floating_islands", 4),
+            features,
+        }
+    }
+
+    pub fn features(&self) -> &TerrainFeatures {
+        &self.features
+    }
+
+    // точка, в которой на самом деле выбирается рельеф
+    pub fn warp(&self, position: [f64; 3]) -> [f64; 3] {
+        let Some(settings) = &self.features.domain_warp else {
+            return position;
+        };
+        let point = position.map(|value| value / settings.scale);
+        [
+            position[0] + self.warp[0].get(point) * settings.strength,
+            position[1] + self.warp[1].get(point) * settings.strength,
+            position[2] + self.warp[2].get(point) * settings.strength,
+        ]
+    }
+
+    fn overhang_density(&self, settings: &OverhangSettings, position: [f64; 3]) -> f64 {
+        let distance = self.terrain.depth(position).unwrap_or(-position[1]).abs();
+        let falloff = 1. - smoothstep(0., settings.band, distance);
+        let point = position.map(|value| value / settings.scale);
+        self.overhangs.get(point) * settings.amplitude * falloff
+    }
+
+    fn island_density(&self, settings: &FloatingIslandSettings, position: [f64; 3]) -> f64 {
+        let point = position.map(|value| value / settings.scale);
+        let shape = (self.islands.get(point) - settings.threshold) * settings.scale * 0.5;
+        // острова сужаются к краям слоя
+        let height = (position[1] - settings.altitude) / settings.thickness;
+        shape - height * height * settings.thickness
+    }
+}
+
+impl DensityFunction for FeatureTerrain {
+    fn sample(&self, position: [f64; 3]) -> f64 {
+        let warped = self.warp(position);
+        let mut density = self.terrain.sample(warped);
+        if let Some(settings) = &self.features.overhangs {
+            density += self.overhang_density(settings, warped);
+        }
+        if let Some(settings) = &self.features.floating_islands {
+            density = density.max(self.island_density(settings, warped));
+        }
+        density
+    }
+
+    fn material(&self, position: [f64; 3]) -> VoxelMaterial {
+        self.terrain.material(self.warp(position))
+    }
+
+    fn surface_height(&self, position: [f64; 2]) -> Option<f64> {
+        self.terrain.surface_height(position)
+    }
+
+    fn water_level(&self, position: [f64; 2]) -> Option<f64> {
+        self.terrain.water_level(position)
+    }
+
+    fn depth(&self, position: [f64; 3]) -> Option<f64> {
+        self.terrain.depth(position)
+    }
+
+    fn up(&self, position: [f64; 3]) -> [f64; 3] {
+        self.terrain.up(position)
+    }
+}
@@ -0,0 +1,131 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Разбиение плоскости XZ на тайлы карты высот для слоев, которые считаются
+/// по большой области сразу (эрозия).
+///
+/// Каждый тайл моделируется на квадрате со стороной в два тайла вокруг своего
+/// центра, а значения четырех соседних тайлов смешиваются билинейно, поэтому
+/// на границах тайлов нет ступенек.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct TileLayout {
+    // число ячеек карты высот на сторону тайла
+    pub tile_cells: usize,
+    // размер ячейки в метрах
+    pub cell_size: f64,
+}
+
+impl TileLayout {
+    // число точек на сторону области моделирования
+    pub fn side(&self) -> usize {
+        self.tile_cells * 2 + 1
+    }
+
+    fn tile_size(&self) -> f64 {
+        self.tile_cells as f64 * self.cell_size
+    }
+
+    // мировые координаты первой точки области моделирования тайла
+    pub fn tile_start(&self, tile: [i64; 2]) -> [f64; 2] {
+        let tile_size = self.tile_size();
+        [
+            (tile[0] as f64 - 0.5) * tile_size,
+            (tile[1] as f64 - 0.5) * tile_size,
+        ]
+    }
+
+    // мировые координаты точки (x, z) области моделирования
+    pub fn point(&self, tile: [i64; 2], x: usize, z: usize) -> [f64; 2] {
+        let start = self.tile_start(tile);
+        [
+            start[0] + x as f64 * self.cell_size,
+            start[1] + z as f64 * self.cell_size,
+        ]
+    }
+
+    // билинейная выборка слоя тайла в мировой точке
+    pub fn sample_layer(&self, tile: [i64; 2], layer: &[f32], position: [f64; 2]) -> f64 {
+        let side = self.side();
+        let start = self.tile_start(tile);
+        let max = (side - 1) as f64 - 1e-9;
+        let x = ((position[0] - start[0]) / self.cell_size).clamp(0., max);
+        let z = ((position[1] - start[1]) / self.cell_size).clamp(0., max);
+        let (cell_x, cell_z) = (x.floor() as usize, z.floor() as usize);
+        let (u, v) = (x - cell_x as f64, z - cell_z as f64);
+        let read = |x: usize, z: usize| layer[x + z * side] as f64;
+        read(cell_x, cell_z) * (1. - u) * (1. - v)
+            + read(cell_x + 1, cell_z) * u * (1. - v)
+            + read(cell_x, cell_z + 1) * (1. - u) * v
+            + read(cell_x + 1, cell_z + 1) * u * v
+    }
+
+    // смешивание значений четырех тайлов, центры которых окружают точку
+    pub fn blend(&self, position: [f64; 2], mut value: impl FnMut([i64; 2]) -> f64) -> f64 {
+        let tile_size = self.tile_size();
+        let grid = [position[0] / tile_size - 0.5, position[1] / tile_size - 0.5];
+        let base = [grid[0].floor() as i64, grid[1].floor() as i64];
+        let (u, v) = (grid[0] - base[0] as f64, grid[1] - base[1] as f64);
+        value(base) * (1. - u) * (1. - v)
+            + value([base[0] + 1, base[1]]) * u * (1. - v)
+            + value([base[0], base[1] + 1]) * (1. - u) * v
+            + value([base[0] + 1, base[1] + 1]) * u * v
+    }
+}
+
+// ячейка тайла: заполняется один раз тем потоком, который первым его запросил
+type TileSlot<T> = Arc<OnceLock<Arc<T>>>;
+
+struct TileCacheInner<T> {
+    tiles: HashMap<[i64; 2], TileSlot<T>>,
+    // от давно не читанных к недавним
+    order: VecDeque<[i64; 2]>,
+}
+
+/// Кэш готовых тайлов, при переполнении вытесняется дольше всех не читанный.
+///
+/// Тайл считается без блокировки всего кэша: другие потоки в это время читают
+/// и считают другие тайлы, а запросившие этот же тайл ждут только его.
+pub(crate) struct TileCache<T> {
+    inner: Mutex<TileCacheInner<T>>,
+    capacity: usize,
+}
+
+impl<T> TileCache<T> {
+    pub fn new(capacity: usize) -> Self {
+        TileCache {
+            inner: Mutex::new(TileCacheInner {
+                tiles: HashMap::new(),
+                order: VecDeque::new(),
+            }),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn get_or_insert_with(&self, tile: [i64; 2], create: impl FnOnce() -> T) -> Arc<T> {
+        let slot = {
+            let mut cache = self.inner.lock().unwrap();
+            match cache.tiles.get(&tile).cloned() {
+                Some(slot) => {
+                    // прочитанный тайл переходит в конец очереди вытеснения
+                    if let Some(index) = cache.order.iter().position(|key| *key == tile) {
+                        cache.order.remove(index);
+                    }
+                    cache.order.push_back(tile);
+                    slot
+                }
+                None => {
+                    if cache.order.len() >= self.capacity {
+                        if let Some(oldest) = cache.order.pop_front() {
+                            cache.tiles.remove(&oldest);
+                        }
+                    }
+                    let slot = TileSlot::default();
+                    cache.order.push_back(tile);
+                    cache.tiles.insert(tile, slot.clone());
+                    slot
+                }
+            }
+        };
+        slot.get_or_init(|| Arc::new(create())).clone()
+    }
+}
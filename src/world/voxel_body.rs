@@ -0,0 +1,183 @@
+use std::sync::Arc;
+
+use bevy::prelude::*;
+
+use super::chunk_from_marching_cubes::{ResolutionOfTheGrid, VoxelChunk};
+use super::density::DensityFunction;
+use super::material::VoxelMaterial;
+
+/// Отдельный ограниченный воксельный объем: астероид, корабль, парящий остров.
+///
+/// Плотность задается в локальных координатах тела, а само тело ставится, двигается
+/// и поворачивается своим `Transform`. Внутри границ объем разбит на обычные
+/// `VoxelChunk`, которые становятся дочерними сущностями тела. За границами
+/// плотность отрицательна, поэтому поверхность всегда замкнута.
+#[derive(Component, Clone)]
+pub struct VoxelBody {
+    density: Arc<BoundedDensity>,
+    resolution: ResolutionOfTheGrid,
+}
+
+impl VoxelBody {
+    // min и max - углы ограничивающего куба в локальных координатах в метрах
+    pub fn new(density: impl DensityFunction + 'static, min: Vec3, max: Vec3) -> Self {
+        VoxelBody::from_arc(Arc::new(density), min, max)
+    }
+
+    pub fn from_arc(density: Arc<dyn DensityFunction>, min: Vec3, max: Vec3) -> Self {
+        VoxelBody {
+            density: Arc::new(BoundedDensity {
+                terrain: density,
+                min: min.min(max).as_dvec3().to_array(),
+                max: min.max(max).as_dvec3().to_array(),
+            }),
+            resolution: ResolutionOfTheGrid::new(33),
+        }
+    }
+
+    // разрешение сетки чанков тела, по умолчанию 33 точки (32 ячейки)
+    pub fn with_resolution(mut self, resolution: ResolutionOfTheGrid) -> Self {
+        self.resolution = resolution;
+        self
+    }
+
+    pub fn resolution(&self) -> ResolutionOfTheGrid {
+        self.resolution
+    }
+
+    // плотность тела вместе с обрезкой по границам, в локальных координатах
+    pub fn density(&self) -> &dyn DensityFunction {
+        self.density.as_ref()
+    }
+
+    pub fn bounds(&self) -> (Vec3, Vec3) {
+        (
+            Vec3::from_array(self.density.min.map(|value| value as f32)),
+            Vec3::from_array(self.density.max.map(|value| value as f32)),
+        )
+    }
+
+    // координаты чанков, которые покрывают ограничивающий куб
+    pub fn chunks(&self) -> Vec<[i128; 3]> {
+        let size = VoxelChunk::size_chunk() as f64;
+        let range = |axis: usize| {
+            (self.density.min[axis] / size).floor() as i128
+                ..=(self.density.max[axis] / size).floor() as i128
+        };
+        let mut chunks = Vec::new();
+        for z in range(2) {
+            for y in range(1) {
+                for x in range(0) {
+                    chunks.push([x, y, z]);
+                }
+            }
+        }
+        chunks
+    }
+
+    // плотность в точке сцены для тела с трансформацией transform
+    pub fn sample_at(&self, transform: &GlobalTransform, point: Vec3) -> f64 {
+        let local = transform.affine().inverse().transform_point3(point);
+        self.density.sample(local.as_dvec3().to_array())
+    }
+}
+
+// плотность тела, обрезанная ограничивающим кубом
+struct BoundedDensity {
+    terrain: Arc<dyn DensityFunction>,
+    min: [f64; 3],
+    max: [f64; 3],
+}
+
+impl BoundedDensity {
+    // расстояние до ближайшей грани куба, положительное внутри
+    fn inside(&self, position: [f64; 3]) -> f64 {
+        (0..3)
+            .map(|axis| (position[axis] - self.min[axis]).min(self.max[axis] - position[axis]))
+            .fold(f64::INFINITY, f64::min)
+    }
+}
+
+impl DensityFunction for BoundedDensity {
+    fn sample(&self, position: [f64; 3]) -> f64 {
+        self.terrain.sample(position).min(self.inside(position))
+    }
+
+    fn sample_column(&self, column: [f64; 2], heights: &[f64], densities: &mut [f64]) {
+        self.terrain.sample_column(column, heights, densities);
+        for (height, density) in heights.iter().zip(densities.iter_mut()) {
+            *density = density.min(self.inside([column[0], *height, column[1]]));
+        }
+    }
+
+    fn material(&self, position: [f64; 3]) -> VoxelMaterial {
+        self.terrain.material(position)
+    }
+
+    fn depth(&self, position: [f64; 3]) -> Option<f64> {
+        self.terrain.depth(position)
+    }
+
+    fn up(&self, position: [f64; 3]) -> [f64; 3] {
+        self.terrain.up(position)
+    }
+}
+
+/// Чанк, принадлежащий `VoxelBody`. Его `Transform` задан относительно тела.
+#[derive(Component)]
+pub struct VoxelBodyChunk;
+
+/// Все, что нужно телу, чтобы его чанки отображались и следовали за его `Transform`.
+#[derive(Bundle)]
+pub struct VoxelBodyBundle {
+    pub body: VoxelBody,
+    pub spatial: SpatialBundle,
+}
+
+impl VoxelBodyBundle {
+    pub fn new(body: VoxelBody, transform: Transform) -> Self {
+        VoxelBodyBundle {
+            body,
+            spatial: SpatialBundle::from_transform(transform),
+        }
+    }
+}
+
+// строит чанки новых и измененных тел; движение тела чанки не перестраивает
+pub(crate) fn mesh_voxel_bodies(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    body_q: Query<(Entity, &VoxelBody), Changed<VoxelBody>>,
+) {
+    for (entity, body) in body_q.iter() {
+        commands.entity(entity).despawn_descendants();
+        let material = materials.add(Color::WHITE.into());
+        for coordinates in body.chunks() {
+            let voxel_chunk = VoxelChunk::new(coordinates);
+            let mesh = voxel_chunk.return_chunk_mesh_parallel(body.density(), body.resolution());
+            // пустые чанки не создаются
+            if mesh.count_vertices() == 0 {
+                continue;
+            }
+            let world_origin = voxel_chunk.world_origin();
+            let translation = Vec3::new(
+                world_origin[0] as f32,
+                world_origin[1] as f32,
+                world_origin[2] as f32,
+            );
+            commands.entity(entity).with_children(|parent| {
+                parent.spawn((
+                    PbrBundle {
+                        mesh: meshes.add(mesh),
+                        material: material.clone(),
+                        transform: Transform::from_translation(translation),
+                        ..default()
+                    },
+                    voxel_chunk,
+                    VoxelBodyChunk,
+                ));
+            });
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use bevy::prelude::*;
+
+use super::caves::CaveSettings;
+use super::erosion::ErosionSettings;
+use super::hydrology::HydrologySettings;
+use super::planet::PlanetSettings;
+use super::random;
+use super::strata::SubsurfaceSettings;
+use super::structures::StructureSettings;
+use super::terrain_features::TerrainFeatures;
+
+/// Зерно генерации мира.
+///
+/// Из одного зерна выводятся независимые под-зерна для каждого слоя шума,
+/// поэтому добавление нового слоя не меняет уже существующие.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct WorldSeed(u64);
+
+impl WorldSeed {
+    pub fn new(value: u64) -> Self {
+        WorldSeed(value)
+    }
+
+    pub fn value(&self) -> u64 {
+        self.0
+    }
+
+    // под-зерно для отдельного слоя, например "surface" или "caves"
+    pub fn sub_seed(&self, layer: &str) -> WorldSeed {
+        // FNV-1a: не зависит от запуска программы, в отличие от стандартного хешера
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for byte in layer.as_bytes() {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+        WorldSeed(random::mix(self.0 ^ hash))
+    }
+
+    // зерно в формате, который принимают генераторы из крейта noise
+    pub fn noise_seed(&self) -> u32 {
+        (self.0 ^ (self.0 >> 32)) as u32
+    }
+}
+
+/// Масштаб рельефа в метрах.
+///
+/// `horizontal` - сколько метров мира приходится на единицу координат шума по x и z,
+/// `vertical` - то же по y и высота поверхности, соответствующая значению шума 1.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TerrainScale {
+    pub horizontal: f64,
+    pub vertical: f64,
+}
+
+impl TerrainScale {
+    // мировые координаты -> координаты шума
+    pub fn to_noise_space(&self, position: [f64; 3]) -> [f64; 3] {
+        [
+            position[0] / self.horizontal,
+            position[1] / self.vertical,
+            position[2] / self.horizontal,
+        ]
+    }
+}
+
+impl Default for TerrainScale {
+    fn default() -> Self {
+        TerrainScale {
+            horizontal: 32.,
+            vertical: 32.,
+        }
+    }
+}
+
+/// Настройки генерации мира.
+///
+/// Ресурс читается при создании `WorldDensity`, поэтому его нужно вставить
+/// до добавления `WorldPlugin`.
+#[derive(Resource, Clone, Debug)]
+pub struct WorldConfig {
+    pub seed: WorldSeed,
+    pub terrain_scale: TerrainScale,
+    // характерный размер одного биома в метрах
+    pub biome_size: f64,
+    // None отключает эрозию
+    pub erosion: Option<ErosionSettings>,
+    // уровень моря, реки и озера; None отключает воду
+    pub hydrology: Option<HydrologySettings>,
+    // искажение координат, навесы и парящие острова
+    pub features: TerrainFeatures,
+    // None отключает пещеры
+    pub caves: Option<CaveSettings>,
+    // виды построек, которые расставляются по миру
+    pub structures: Vec<StructureSettings>,
+    // пласты породы и руды; None оставляет материал поверхности на любой глубине
+    pub subsurface: Option<SubsurfaceSettings>,
+    // сферическая планета вместо бесконечной плоскости; None - плоский мир
+    pub planet: Option<PlanetSettings>,
+}
+
+impl Default for WorldConfig {
+    fn default() -> Self {
+        WorldConfig {
+            seed: WorldSeed::default(),
+            terrain_scale: TerrainScale::default(),
+            biome_size: 512.,
+            erosion: None,
+            hydrology: None,
+            features: TerrainFeatures::default(),
+            caves: None,
+            structures: Vec::new(),
+            subsurface: None,
+            planet: None,
+        }
+    }
+}
+
+impl WorldConfig {
+    pub fn with_seed(seed: u64) -> Self {
+        WorldConfig {
+            seed: WorldSeed::new(seed),
+            ..default()
+        }
+    }
+
+    // мир со всеми слоями: вода, пещеры и пласты породы. По умолчанию они
+    // выключены, потому что заметно замедляют генерацию; эрозия самая дорогая
+    // и включается отдельно
+    pub fn full() -> Self {
+        WorldConfig {
+            hydrology: Some(HydrologySettings::default()),
+            caves: Some(CaveSettings::default()),
+            subsurface: Some(SubsurfaceSettings::default()),
+            ..default()
+        }
+    }
+}
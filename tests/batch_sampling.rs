@@ -0,0 +1,89 @@
+use std::sync::Arc;
+
+use bevy_voxel_engine::{
+    BiomeTerrain, CsgOperation, DensityFunction, Heightmap, HeightmapSampling, HeightmapSettings,
+    HeightmapTerrain, ResolutionOfTheGrid, Sphere, StructureKind, StructureSettings, TerrainScale,
+    VoxelChunk, VoxelMaterial, WorldConfig, WorldDensity, WorldSeed,
+};
+
+// столбцы должны давать в точности то же, что и отдельные точки
+fn assert_columns_match(density: &dyn DensityFunction) {
+    let heights: Vec<f64> = (0..40).map(|y| y as f64 * 1.7 - 30.).collect();
+    let mut column = vec![0.; heights.len()];
+    for (x, z) in [(3., -7.), (120.5, 64.), (-250., 411.25)] {
+        density.sample_column([x, z], &heights, &mut column);
+        for (height, value) in heights.iter().zip(&column) {
+            assert_eq!(value.to_bits(), density.sample([x, *height, z]).to_bits());
+        }
+    }
+}
+
+#[test]
+fn default_pipeline_columns_match_points() {
+    let config = WorldConfig {
+        structures: vec![StructureSettings {
+            name: "boulder".to_string(),
+            kind: StructureKind::Stamp {
+                shape: Arc::new(Sphere {
+                    center: [0., 0., 0.],
+                    radius: 4.,
+                }),
+                operation: CsgOperation::Union,
+                material: VoxelMaterial::Rock,
+            },
+            spacing: 16.,
+            chance: 1.,
+            max_slope: 10.,
+            biomes: Vec::new(),
+            avoid_water: false,
+            radius: 5.,
+        }],
+        seed: WorldSeed::new(17),
+        ..WorldConfig::full()
+    };
+    assert_columns_match(WorldDensity::from_config(&config).function());
+}
+
+#[test]
+fn heightmap_columns_match_points_across_the_border() {
+    let data = (0..64).map(|index| (index % 8) as f32 / 7.).collect();
+    let terrain = HeightmapTerrain::new(
+        Heightmap::new(8, 8, data),
+        HeightmapSettings {
+            origin: [0., 0.],
+            extents: [100., 100.],
+            base_height: -5.,
+            vertical_scale: 20.,
+            sampling: HeightmapSampling::Bicubic,
+            border_blend: 200.,
+        },
+        Some(Arc::new(BiomeTerrain::new(
+            WorldSeed::new(2),
+            TerrainScale::default(),
+            512.,
+        ))),
+    );
+    assert_columns_match(&terrain);
+}
+
+#[test]
+fn voxel_grid_matches_point_sampling() {
+    let density = WorldDensity::from_config(&WorldConfig {
+        seed: WorldSeed::new(5),
+        ..WorldConfig::full()
+    });
+    let chunk = VoxelChunk::new([3, -1, 7]);
+    let grid = chunk.sample_voxel_grid(density.function(), ResolutionOfTheGrid::new(9));
+    for z in 0..9 {
+        for y in 0..9 {
+            for x in 0..9 {
+                let position = [
+                    (x + 3 * 8) as f64 * 4.,
+                    (y as f64 - 8.) * 4.,
+                    (z + 7 * 8) as f64 * 4.,
+                ];
+                assert_eq!(grid.read(x, y, z), density.sample(position) as f32);
+            }
+        }
+    }
+}
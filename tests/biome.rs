@@ -0,0 +1,44 @@
+use std::collections::HashSet;
+
+use bevy_voxel_engine::{Biome, BiomeTerrain, TerrainScale, WorldSeed};
+
+fn terrain() -> BiomeTerrain {
+    BiomeTerrain::new(WorldSeed::new(5), TerrainScale::default(), 512.)
+}
+
+#[test]
+fn biome_weights_are_normalised() {
+    let terrain = terrain();
+    for position in [[0., 0.], [1000., -250.], [-7321.5, 4410.25]] {
+        let sum: f64 = terrain.biome_map().weights(position).iter().sum();
+        assert!((sum - 1.).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn every_biome_appears_in_a_large_area() {
+    let terrain = terrain();
+    let mut found = HashSet::new();
+    for z in -40..40 {
+        for x in -40..40 {
+            found.insert(
+                terrain
+                    .biome_map()
+                    .biome_at([x as f64 * 256., z as f64 * 256.]),
+            );
+        }
+    }
+    assert_eq!(found.len(), Biome::ALL.len());
+}
+
+#[test]
+fn surface_is_continuous_across_biome_borders() {
+    let terrain = terrain();
+    // на отрезке в 1 мм поверхность не должна скачком менять высоту
+    for step in 0..20_000 {
+        let x = step as f64 * 0.5 - 5000.;
+        let jump =
+            (terrain.surface_height([x + 0.001, 31.]) - terrain.surface_height([x, 31.])).abs();
+        assert!(jump < 0.05, "surface jumps by {jump} m at x = {x}");
+    }
+}
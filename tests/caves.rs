@@ -0,0 +1,50 @@
+use std::sync::Arc;
+
+use bevy_voxel_engine::{CaveCarver, CaveSettings, DensityFunction, WorldSeed};
+
+// ровная поверхность на высоте 0
+struct Flat;
+
+impl DensityFunction for Flat {
+    fn sample(&self, position: [f64; 3]) -> f64 {
+        -position[1]
+    }
+
+    fn surface_height(&self, _position: [f64; 2]) -> Option<f64> {
+        Some(0.)
+    }
+}
+
+fn carver(settings: CaveSettings) -> CaveCarver {
+    CaveCarver::new(Arc::new(Flat), WorldSeed::new(9), settings)
+}
+
+fn grid(y: f64) -> impl Iterator<Item = [f64; 3]> {
+    (0..120).flat_map(move |z| (0..120).map(move |x| [x as f64 * 4., y, z as f64 * 4.]))
+}
+
+#[test]
+fn caves_stay_within_depth_range() {
+    let settings = CaveSettings {
+        surface_openings: 0.,
+        ..Default::default()
+    };
+    let caves = carver(settings.clone());
+
+    let shallow = -(settings.min_depth - settings.fade - 0.5);
+    assert!(grid(shallow).all(|point| caves.sample(point) > 0.));
+    let deep = -(settings.max_depth + settings.fade + 0.5);
+    assert!(grid(deep).all(|point| caves.sample(point) > 0.));
+
+    let middle = -(settings.min_depth + settings.max_depth) / 2.;
+    assert!(grid(middle).any(|point| caves.sample(point) < 0.));
+}
+
+#[test]
+fn caves_open_to_daylight_at_entrances() {
+    let caves = carver(CaveSettings {
+        surface_openings: 0.5,
+        ..Default::default()
+    });
+    assert!(grid(-0.5).any(|point| caves.sample(point) < 0.));
+}
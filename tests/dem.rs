@@ -0,0 +1,150 @@
+use bevy_voxel_engine::{
+    Dem, DemError, DemSettings, DensityFunction, HeightmapSampling, RawDemLayout, RawDemSample,
+    ResolutionOfTheGrid, VoxelChunk,
+};
+
+const ASC: &str = "ncols 3
+nrows 2
+xllcorner 100.0
+yllcorner 200.0
+cellsize 10
+NODATA_value -9999
+100 110 120
+130 -9999 150
+";
+
+fn settings() -> DemSettings {
+    DemSettings {
+        metres_per_voxel: 2.,
+        origin: Some([0., 0.]),
+        sampling: HeightmapSampling::Bilinear,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn esri_ascii_grid_is_parsed() {
+    let dem = Dem::from_esri_ascii(ASC).unwrap();
+    assert_eq!((dem.width(), dem.height(), dem.cell_size()), (3, 2, 10.));
+    assert_eq!(dem.elevation(2, 0), 120.);
+    // пропуск NODATA заполняется наименьшей высотой
+    assert_eq!(dem.elevation(1, 1), 100.);
+    // значения стоят в центрах ячеек
+    assert_eq!(dem.corner(), [105., 205.]);
+    assert!(matches!(
+        Dem::from_esri_ascii("ncols 2\nnrows 2\ncellsize 1\n1 2 3"),
+        Err(DemError::SizeMismatch { .. })
+    ));
+}
+
+#[test]
+fn raw_dems_match_the_ascii_grid() {
+    let elevations = [100., 110., 120., 130., 100., 150.];
+    let layout = |sample, big_endian| RawDemLayout {
+        width: 3,
+        height: 2,
+        sample,
+        big_endian,
+        cell_size: 10.,
+        nodata: None,
+    };
+    let u16_bytes: Vec<u8> = elevations
+        .iter()
+        .flat_map(|value| (*value as u16).to_be_bytes())
+        .collect();
+    let f32_bytes: Vec<u8> = elevations
+        .iter()
+        .flat_map(|value| (*value as f32).to_le_bytes())
+        .collect();
+    let ascii = Dem::from_esri_ascii(ASC).unwrap();
+    for dem in [
+        Dem::from_raw(&u16_bytes, &layout(RawDemSample::U16, true)).unwrap(),
+        Dem::from_raw(&f32_bytes, &layout(RawDemSample::F32, false)).unwrap(),
+    ] {
+        for y in 0..2 {
+            for x in 0..3 {
+                assert_eq!(dem.elevation(x, y), ascii.elevation(x, y));
+            }
+        }
+    }
+}
+
+#[test]
+fn elevations_are_scaled_to_voxels() {
+    let terrain = Dem::from_esri_ascii(ASC).unwrap().to_terrain(&settings());
+    // 2 реальных метра на воксель: шаг сетки 5, наименьшая высота на y = 0
+    assert!((terrain.surface_height([0., 0.]).unwrap() - 0.).abs() < 1e-4);
+    assert!((terrain.surface_height([10., 0.]).unwrap() - 10.).abs() < 1e-4);
+    assert!((terrain.surface_height([0., 5.]).unwrap() - 15.).abs() < 1e-4);
+    assert!(terrain.sample([10., 9., 0.]) > 0. && terrain.sample([10., 11., 0.]) < 0.);
+}
+
+#[test]
+fn dem_tile_builds_chunk_meshes() {
+    let text = std::fs::read_to_string("assets/terrain/valley.asc").unwrap();
+    let dem = Dem::from_esri_ascii(&text).unwrap();
+    let terrain = dem.to_terrain(&DemSettings {
+        metres_per_voxel: 30.,
+        ..Default::default()
+    });
+    let mut chunk = VoxelChunk::new([0, 0, 0]);
+    let grid = chunk.sample_voxel_grid(&terrain, ResolutionOfTheGrid::new(9));
+    assert!(grid.data().iter().any(|value| *value > 0.));
+    assert!(grid.data().iter().any(|value| *value < 0.));
+    chunk.return_chunk_mesh(&terrain, ResolutionOfTheGrid::new(9));
+}
+
+#[test]
+fn single_row_dems_are_rejected() {
+    assert!(matches!(
+        Dem::from_esri_ascii("ncols 3\nnrows 1\ncellsize 10\n100 110 120"),
+        Err(DemError::TooSmall {
+            width: 3,
+            height: 1
+        })
+    ));
+    let layout = RawDemLayout {
+        width: 1,
+        height: 4,
+        sample: RawDemSample::U16,
+        big_endian: false,
+        cell_size: 1.,
+        nodata: None,
+    };
+    assert!(matches!(
+        Dem::from_raw(&[0; 8], &layout),
+        Err(DemError::TooSmall { .. })
+    ));
+}
+
+#[test]
+fn adjacent_tiles_line_up_by_their_georeference() {
+    // восточный сосед ASC: его западный столбец совпадает с восточным столбцом ASC
+    let east = "ncols 2
+nrows 2
+xllcenter 125.0
+yllcenter 205.0
+cellsize 10
+120 125
+150 160
+";
+    let settings = DemSettings {
+        metres_per_voxel: 2.,
+        geo_origin: Some([105., 215.]),
+        sampling: HeightmapSampling::Bilinear,
+        datum: Some(0.),
+        ..Default::default()
+    };
+    let west = Dem::from_esri_ascii(ASC).unwrap().to_terrain(&settings);
+    let east = Dem::from_esri_ascii(east).unwrap().to_terrain(&settings);
+    // geo_origin - северо-западное значение ASC
+    assert_eq!(west.settings().origin, [0., 0.]);
+    assert_eq!(east.settings().origin, [10., 0.]);
+    for z in [0., 2.5, 5.] {
+        let shared = [10., z];
+        assert!(
+            (west.surface_height(shared).unwrap() - east.surface_height(shared).unwrap()).abs()
+                < 1e-4
+        );
+    }
+}
@@ -0,0 +1,63 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use bevy_voxel_engine::{DensityCache, DensityFunction, ResolutionOfTheGrid, VoxelChunk};
+
+// считает обращения к плотности
+#[derive(Default)]
+struct Counting(AtomicUsize);
+
+impl DensityFunction for Counting {
+    fn sample(&self, position: [f64; 3]) -> f64 {
+        self.0.fetch_add(1, Ordering::Relaxed);
+        (position[0] * 0.3).sin() * 4. - position[1] + position[2] * 0.1
+    }
+}
+
+#[test]
+fn repeated_requests_reuse_the_grid() {
+    let density = Counting::default();
+    let mut cache = DensityCache::new(8);
+    let chunk = VoxelChunk::new([2, 0, -1]);
+    let first = cache.get_or_sample(&chunk, &density, ResolutionOfTheGrid::new(17));
+    let samples = density.0.load(Ordering::Relaxed);
+    assert_eq!(samples, 17 * 17 * 17);
+    let second = cache.get_or_sample(&chunk, &density, ResolutionOfTheGrid::new(17));
+    assert!(Arc::ptr_eq(&first, &second));
+    assert_eq!(density.0.load(Ordering::Relaxed), samples);
+
+    cache.invalidate([2, 0, -1]);
+    assert!(cache.is_empty());
+}
+
+#[test]
+fn lower_lods_are_downsampled_from_cached_grids() {
+    let density = Counting::default();
+    let mut cache = DensityCache::new(8);
+    let chunk = VoxelChunk::new([-3, 1, 5]);
+    cache.get_or_sample(&chunk, &density, ResolutionOfTheGrid::new(33));
+    let samples = density.0.load(Ordering::Relaxed);
+    let coarse = cache.get_or_sample(&chunk, &density, ResolutionOfTheGrid::new(9));
+    assert_eq!(density.0.load(Ordering::Relaxed), samples);
+
+    // прореженная сетка совпадает с опрошенной напрямую
+    let direct = chunk.sample_voxel_grid(&density, ResolutionOfTheGrid::new(9));
+    assert_eq!(coarse.data(), direct.data());
+}
+
+#[test]
+fn least_recently_used_grid_is_evicted() {
+    let density = Counting::default();
+    let mut cache = DensityCache::new(2);
+    let resolution = ResolutionOfTheGrid::new(5);
+    for x in 0..2 {
+        cache.get_or_sample(&VoxelChunk::new([x, 0, 0]), &density, resolution);
+    }
+    // чанк 0 использован последним, вытесняется чанк 1
+    cache.get_or_sample(&VoxelChunk::new([0, 0, 0]), &density, resolution);
+    cache.get_or_sample(&VoxelChunk::new([2, 0, 0]), &density, resolution);
+    assert_eq!(cache.len(), 2);
+    assert!(cache.get([0, 0, 0], 5).is_some());
+    assert!(cache.get([1, 0, 0], 5).is_none());
+    assert!(cache.get([2, 0, 0], 5).is_some());
+}
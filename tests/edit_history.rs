@@ -0,0 +1,93 @@
+use bevy_voxel_engine::{
+    Brush, BrushOperation, SculptBrush, SculptTool, VoxelEdits, VoxelMaterial,
+};
+
+// ровная земля на y = 0
+fn flat(position: [f64; 3]) -> f64 {
+    -position[1]
+}
+
+fn bits(edits: &VoxelEdits, coordinates: [i128; 3]) -> Option<Vec<u32>> {
+    edits
+        .grid(&flat, coordinates)
+        .map(|grid| grid.data().iter().map(|value| value.to_bits()).collect())
+}
+
+#[test]
+fn undo_restores_byte_identical_density() {
+    let mut edits = VoxelEdits::default();
+    edits.apply_brush(
+        &flat,
+        &Brush::sphere([16., 0., 16.], 5.),
+        BrushOperation::Remove,
+        1.,
+    );
+    let before = bits(&edits, [0, 0, 0]);
+    let delta = edits.delta([0, 0, 0]).cloned();
+
+    let touched = edits.apply_brush(
+        &flat,
+        &Brush::sphere([18., 2., 16.], 4.),
+        BrushOperation::Add,
+        0.6,
+    );
+    assert_ne!(bits(&edits, [0, 0, 0]), before);
+    for coordinates in &touched {
+        edits.mark_clean(*coordinates);
+    }
+
+    // отмена перестраивает только чанки второй кисти
+    assert_eq!(edits.undo(), touched);
+    assert_eq!(bits(&edits, [0, 0, 0]), before);
+    assert_eq!(edits.delta([0, 0, 0]).cloned(), delta);
+
+    edits.undo();
+    assert!(edits.edited_chunks().next().is_none());
+    assert!(!edits.can_undo());
+
+    edits.redo();
+    edits.redo();
+    assert!(!edits.can_redo());
+    assert_ne!(bits(&edits, [0, 0, 0]), before);
+}
+
+#[test]
+fn stroke_is_undone_in_one_step() {
+    let mut edits = VoxelEdits::default();
+    edits.begin_stroke();
+    for step in 0..5 {
+        edits.apply_brush(
+            &flat,
+            &Brush::sphere([step as f64 * 2., 0., 4.], 2.),
+            BrushOperation::Remove,
+            0.5,
+        );
+    }
+    edits.sculpt(
+        &flat,
+        &SculptBrush::new([4., 0., 4.], 3.),
+        &SculptTool::Paint(VoxelMaterial::Snow),
+    );
+    edits.end_stroke();
+    assert!(edits.edited_chunks().next().is_some());
+
+    edits.undo();
+    assert!(edits.edited_chunks().next().is_none());
+    assert!(!edits.can_undo());
+    assert!(edits.can_redo());
+}
+
+#[test]
+fn new_edit_clears_redo() {
+    let mut edits = VoxelEdits::default();
+    let brush = Brush::sphere([0., 0., 0.], 3.);
+    edits.apply_brush(&flat, &brush, BrushOperation::Add, 1.);
+    edits.undo();
+    assert!(edits.can_redo());
+    edits.apply_brush(&flat, &brush, BrushOperation::Remove, 1.);
+    assert!(!edits.can_redo());
+    // правка без изменений не создает шаг
+    edits.apply_brush(&flat, &brush, BrushOperation::Remove, 0.);
+    edits.undo();
+    assert!(!edits.can_undo());
+}
@@ -0,0 +1,132 @@
+use bevy_voxel_engine::{
+    Brush, BrushOperation, DensityFunction, Falloff, ResolutionOfTheGrid, SculptBrush, SculptTool,
+    VoxelChunk, VoxelEdits, VoxelMaterial,
+};
+
+// ровная земля на y = 0
+fn flat(position: [f64; 3]) -> f64 {
+    -position[1]
+}
+
+#[test]
+fn edits_store_only_changed_points() {
+    let mut edits = VoxelEdits::default();
+    edits.apply_brush(
+        &flat,
+        &Brush::sphere([40., 0., 40.], 3.),
+        BrushOperation::Remove,
+        1.,
+    );
+    // яма лежит внутри одного столбца чанков
+    let mut edited: Vec<_> = edits.edited_chunks().copied().collect();
+    edited.sort();
+    assert_eq!(edited, vec![[1, -1, 1], [1, 0, 1]]);
+    let points: usize = edited
+        .iter()
+        .map(|coordinates| edits.delta(*coordinates).unwrap().density.len())
+        .sum();
+    // не больше точек, чем в кубе кисти
+    assert!(points > 0 && points <= 7 * 7 * 7);
+    // углы куба кисти далеко от сферы и не хранятся
+    assert!(edits.override_at([37, -3, 37]).is_none());
+    // соседние чанки остаются процедурными
+    assert!(edits.grid(&flat, [3, 0, 3]).is_none());
+
+    // возврат к процедурной плотности убирает дельты целиком
+    edits.sculpt(
+        &flat,
+        &SculptBrush::new([40., 0., 40.], 5.).with_falloff(Falloff::Constant),
+        &SculptTool::Level { height: 0. },
+    );
+    assert!(edits.edited_chunks().next().is_none());
+}
+
+#[test]
+fn edited_density_reads_overrides_between_points() {
+    let mut edits = VoxelEdits::default();
+    edits.apply_brush(
+        &flat,
+        &Brush::sphere([8., 0., 8.], 4.),
+        BrushOperation::Remove,
+        1.,
+    );
+    let edited = edits.over(&flat);
+    let value = edits.value_at(&flat, [8, -2, 8]);
+    assert!(value < 0.);
+    assert_eq!(edited.sample([8., -2., 8.]), value as f64);
+    // между точками значение лежит между соседними
+    let below = edits.value_at(&flat, [8, -3, 8]) as f64;
+    let between = edited.sample([8., -2.5, 8.]);
+    assert!(between > value.min(below as f32) as f64 && between < below.max(value as f64));
+    // вдали от правок - процедурная плотность
+    assert_eq!(
+        edited.sample([100.3, 1.7, 5.]),
+        flat.sample([100.3, 1.7, 5.])
+    );
+}
+
+#[test]
+fn ron_save_holds_deltas_and_restores_them() {
+    let mut edits = VoxelEdits::default();
+    edits.apply_brush(
+        &flat,
+        &Brush::sphere([-20., 1., 3.], 3.5),
+        BrushOperation::Add,
+        0.7,
+    );
+    edits.sculpt(
+        &flat,
+        &SculptBrush::new([-20., 0., 3.], 2.),
+        &SculptTool::Paint(VoxelMaterial::Iron),
+    );
+    let save = edits.to_ron().unwrap();
+    let loaded = VoxelEdits::from_ron(&save).unwrap();
+
+    let mut chunks: Vec<_> = edits.edited_chunks().copied().collect();
+    chunks.sort();
+    let mut loaded_chunks: Vec<_> = loaded.edited_chunks().copied().collect();
+    loaded_chunks.sort();
+    assert_eq!(chunks, loaded_chunks);
+    for coordinates in chunks {
+        assert_eq!(edits.delta(coordinates), loaded.delta(coordinates));
+        // загруженные чанки перестроятся
+        assert!(loaded.is_dirty(coordinates));
+    }
+    assert_eq!(
+        loaded.painted_material([-20, 0, 3]),
+        Some(VoxelMaterial::Iron)
+    );
+}
+
+#[test]
+fn small_edits_reach_coarse_lod_grids() {
+    let mut edits = VoxelEdits::default();
+    // ямка в метр между узлами сетки с шагом 4 м
+    edits.apply_brush(
+        &flat,
+        &Brush::sphere([6., -1., 6.], 1.),
+        BrushOperation::Remove,
+        1.,
+    );
+    let chunk = VoxelChunk::new([0, -1, 0]);
+    let procedural = chunk.sample_voxel_grid(&flat, ResolutionOfTheGrid::new(9));
+    let mut coarse = procedural.clone();
+    edits.apply(&flat, &chunk, &mut coarse);
+    let changed = (0..9)
+        .flat_map(|z| (0..9).flat_map(move |y| (0..9).map(move |x| [x, y, z])))
+        .filter(|node| {
+            coarse.read(node[0], node[1], node[2]) != procedural.read(node[0], node[1], node[2])
+        })
+        .count();
+    assert!(changed > 0);
+    // изменение не сильнее самой правки
+    let deepest = edits.value_at(&flat, [6, -1, 6]) as f64 - flat.sample([6., -1., 6.]);
+    for z in 0..9 {
+        for y in 0..9 {
+            for x in 0..9 {
+                let change = coarse.read(x, y, z) as f64 - procedural.read(x, y, z) as f64;
+                assert!(change >= deepest - 1e-4 && change <= 0.);
+            }
+        }
+    }
+}
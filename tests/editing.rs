@@ -0,0 +1,79 @@
+use bevy::prelude::*;
+use bevy_voxel_engine::{
+    Brush, BrushOperation, DensityCache, DensityFunction, VoxelEdits, VoxelWorld, WorldDensity,
+};
+
+// ровная земля на y = 0
+fn flat(position: [f64; 3]) -> f64 {
+    -position[1]
+}
+
+#[test]
+fn digging_across_chunk_borders_leaves_no_seams() {
+    let mut edits = VoxelEdits::default();
+    // яма на углу четырех чанков по x и z
+    let touched = edits.apply_brush(
+        &flat,
+        &Brush::sphere([32., 0., 32.], 5.),
+        BrushOperation::Remove,
+        1.,
+    );
+    // чанки [0..1] x [-1..0] x [0..1]
+    assert_eq!(touched.len(), 8);
+    let grid = edits.grid(&flat, [1, -1, 1]).unwrap();
+    assert!(grid.read(0, 30, 0) < 0.);
+    // вне кисти рельеф не меняется
+    assert_eq!(grid.read(10, 31, 10), 1.);
+
+    // общие точки соседних чанков совпадают
+    let left = edits.grid(&flat, [0, -1, 1]).unwrap();
+    let right = edits.grid(&flat, [1, -1, 1]).unwrap();
+    for z in 0..VoxelEdits::resolution() {
+        for y in 0..VoxelEdits::resolution() {
+            assert_eq!(left.read(32, y, z), right.read(0, y, z));
+        }
+    }
+}
+
+#[test]
+fn strength_blends_towards_the_brush() {
+    let mut edits = VoxelEdits::default();
+    assert!(edits
+        .apply_brush(
+            &flat,
+            &Brush::sphere([5., 5., 5.], 3.),
+            BrushOperation::Add,
+            0.
+        )
+        .is_empty());
+    edits.apply_brush(
+        &flat,
+        &Brush::sphere([5., 5., 5.], 3.),
+        BrushOperation::Add,
+        0.5,
+    );
+    // в центре плотность была -5, фигура дает +3
+    let value = edits.grid(&flat, [0, 0, 0]).unwrap().read(5, 5, 5);
+    assert_eq!(value, -1.);
+    assert!(edits.is_dirty([0, 0, 0]));
+}
+
+#[test]
+fn voxel_world_applies_brushes_from_systems() {
+    let mut app = App::new();
+    app.insert_resource(WorldDensity::new(flat))
+        .init_resource::<DensityCache>()
+        .init_resource::<VoxelEdits>()
+        .add_systems(Update, |mut world: VoxelWorld| {
+            world.apply_brush(
+                &Brush::sphere([10., 0., 10.], 4.),
+                BrushOperation::Remove,
+                1.,
+            );
+        });
+    app.update();
+    let edits = app.world.resource::<VoxelEdits>();
+    assert!(edits.is_edited([0, -1, 0]) && edits.is_edited([0, 0, 0]));
+    assert!(edits.grid(&flat, [0, 0, 0]).unwrap().read(10, 0, 10) < 0.);
+    assert_eq!(flat.sample([10., 0., 10.]), 0.);
+}
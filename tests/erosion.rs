@@ -0,0 +1,83 @@
+use std::sync::Arc;
+
+use bevy_voxel_engine::{
+    BiomeTerrain, DensityFunction, ErodedTerrain, ErosionSettings, SurfaceTerrain, TerrainScale,
+    WorldSeed,
+};
+
+fn eroded(seed: u64) -> ErodedTerrain {
+    let settings = ErosionSettings {
+        tile_cells: 32,
+        ..Default::default()
+    };
+    let terrain = BiomeTerrain::new(WorldSeed::new(seed), TerrainScale::default(), 512.);
+    ErodedTerrain::new(Arc::new(terrain), WorldSeed::new(seed), settings)
+}
+
+#[test]
+fn erosion_changes_the_surface() {
+    let terrain = eroded(4);
+    let changed = (0..64)
+        .map(|i| terrain.erosion_delta([i as f64 * 3.1, i as f64 * 1.7]))
+        .filter(|delta| delta.abs() > 1e-3)
+        .count();
+    assert!(changed > 0);
+}
+
+#[test]
+fn erosion_is_deterministic_and_independent_of_order() {
+    let points: Vec<[f64; 2]> = (0..40)
+        .map(|i| [i as f64 * 13.3 - 200., i as f64 * 7.9])
+        .collect();
+    let first = eroded(8);
+    let forward: Vec<u64> = points
+        .iter()
+        .map(|point| first.surface_height(*point).unwrap().to_bits())
+        .collect();
+    let second = eroded(8);
+    let mut backward: Vec<u64> = points
+        .iter()
+        .rev()
+        .map(|point| second.surface_height(*point).unwrap().to_bits())
+        .collect();
+    backward.reverse();
+    assert_eq!(forward, backward);
+}
+
+#[test]
+fn eroded_surface_is_continuous_across_tiles() {
+    let terrain = eroded(2);
+    let tile_size = 32. * ErosionSettings::default().cell_size;
+    for tile in -2..=2 {
+        // граница тайлов и центр тайла
+        for x in [tile as f64 * tile_size, (tile as f64 + 0.5) * tile_size] {
+            let jump = (terrain.surface_height([x + 0.001, 5.]).unwrap()
+                - terrain.surface_height([x - 0.001, 5.]).unwrap())
+            .abs();
+            assert!(jump < 0.05, "surface jumps by {jump} m at x = {x}");
+        }
+    }
+}
+
+#[test]
+fn eroded_density_crosses_zero_at_the_eroded_height() {
+    // у любого рельефа с surface_height плотность у поверхности в метрах,
+    // поэтому сдвиг высоты переносит поверхность ровно на столько же
+    let terrain = ErodedTerrain::new(
+        Arc::new(SurfaceTerrain::new(
+            WorldSeed::new(6),
+            TerrainScale::default(),
+        )),
+        WorldSeed::new(6),
+        ErosionSettings {
+            tile_cells: 32,
+            ..Default::default()
+        },
+    );
+    for i in 0..16 {
+        let column = [i as f64 * 5.3, i as f64 * -3.1];
+        let height = terrain.surface_height(column).unwrap();
+        let density = terrain.sample([column[0], height, column[1]]);
+        assert!(density.abs() < 1e-9, "density {density} at the surface");
+    }
+}
@@ -0,0 +1,38 @@
+use bevy::prelude::Vec3;
+use bevy_voxel_engine::{FloatingOrigin, VoxelChunk};
+
+#[test]
+fn shift_waits_for_the_threshold_and_snaps_to_chunks() {
+    let origin = FloatingOrigin::new(100.);
+    assert_eq!(origin.shift_for(Vec3::new(99., -99.5, 0.)), None);
+    assert_eq!(
+        origin.shift_for(Vec3::new(130.5, -40., 7.)),
+        Some([128, -64, 0])
+    );
+    // после сдвига камера оказывается в пределах чанка от нуля
+    let size = VoxelChunk::size_chunk() as f32;
+    let shift = origin.shift_for(Vec3::new(-250., 20., 1000.)).unwrap();
+    let remainder =
+        Vec3::new(-250., 20., 1000.) - Vec3::new(shift[0] as f32, shift[1] as f32, shift[2] as f32);
+    assert!(remainder.min_element() >= 0. && remainder.max_element() < size);
+}
+
+#[test]
+fn origin_accumulates_beyond_the_f32_and_i32_range() {
+    let mut origin = FloatingOrigin::new(100.);
+    let far = Vec3::new(10_000_000., 0., -10_000_000.);
+    for _ in 0..300 {
+        let shift = origin.shift_for(far).unwrap();
+        origin.shift(shift);
+    }
+    assert_eq!(origin.origin(), [3_000_000_000, 0, -3_000_000_000]);
+
+    // точка сцены рядом с нулем переводится в мир и обратно без потерь
+    let translation = Vec3::new(12.25, -3.5, 0.125);
+    let world = origin.scene_to_world(translation);
+    assert_eq!(world, [3_000_000_012.25, -3.5, -2_999_999_999.875]);
+    assert_eq!(origin.world_to_scene(world), translation);
+    // угол чанка стоит там же, где его мировые координаты
+    let chunk = VoxelChunk::new([93_750_000, 0, -93_750_000]);
+    assert_eq!(origin.chunk_translation(&chunk), Vec3::ZERO);
+}
@@ -0,0 +1,108 @@
+use std::sync::Arc;
+
+use bevy::render::texture::{CompressedImageFormats, Image, ImageType};
+use bevy_voxel_engine::{
+    DensityFunction, Heightmap, HeightmapSampling, HeightmapSettings, HeightmapTerrain,
+    StructureKind, StructureSettings, WorldConfig, WorldDensity,
+};
+
+fn settings(sampling: HeightmapSampling) -> HeightmapSettings {
+    HeightmapSettings {
+        origin: [0., 0.],
+        extents: [30., 30.],
+        base_height: 0.,
+        vertical_scale: 10.,
+        sampling,
+        border_blend: 20.,
+    }
+}
+
+// 4x4 пикселя, значение растет вдоль x
+fn ramp() -> Heightmap {
+    let data = (0..16).map(|index| (index % 4) as f32 / 3.).collect();
+    Heightmap::new(4, 4, data)
+}
+
+#[test]
+fn sixteen_bit_png_loads_through_the_image_loader() {
+    let bytes = std::fs::read("assets/terrain/island.png").unwrap();
+    let image = Image::from_buffer(
+        &bytes,
+        ImageType::Extension("png"),
+        CompressedImageFormats::NONE,
+        false,
+    )
+    .unwrap();
+    let heightmap = Heightmap::from_image(&image).expect("16-bit grayscale should be supported");
+    assert_eq!((heightmap.width(), heightmap.height()), (64, 64));
+    // в центре изображения холм
+    assert!(heightmap.pixel(32, 32) > heightmap.pixel(0, 0) + 0.5);
+}
+
+#[test]
+fn sampling_matches_pixels_and_interpolates_between_them() {
+    for sampling in [HeightmapSampling::Bilinear, HeightmapSampling::Bicubic] {
+        let terrain = HeightmapTerrain::new(ramp(), settings(sampling), None);
+        // пиксели стоят через 10 м
+        assert!((terrain.surface_height([10., 10.]).unwrap() - 10. / 3.).abs() < 1e-5);
+        assert!((terrain.surface_height([15., 10.]).unwrap() - 5.).abs() < 1e-5);
+        assert!(terrain.sample([15., 4., 10.]) > 0.);
+        assert!(terrain.sample([15., 6., 10.]) < 0.);
+    }
+}
+
+#[test]
+fn procedural_terrain_continues_beyond_the_borders() {
+    let fallback: Arc<dyn DensityFunction> = Arc::new(|position: [f64; 3]| 50. - position[1]);
+    let terrain = HeightmapTerrain::new(
+        ramp(),
+        settings(HeightmapSampling::Bilinear),
+        Some(fallback),
+    );
+    // на изображении фон не влияет, за полосой перехода остается только он
+    assert!(terrain.sample([15., 4., 10.]) > 0. && terrain.sample([15., 6., 10.]) < 0.);
+    assert_eq!(terrain.sample([100., 49., 10.]), 1.);
+    // в полосе перехода плотность между двумя рельефами
+    let blended = terrain.sample([40., 30., 10.]);
+    assert!(blended > 10. - 30. && blended < 50. - 30.);
+    // у фона нет высоты поверхности, и в полосе перехода ее тоже нет
+    assert!((terrain.surface_height([15., 10.]).unwrap() - 5.).abs() < 1e-5);
+    assert_eq!(terrain.surface_height([40., 10.]), None);
+    assert_eq!(terrain.surface_height([100., 10.]), None);
+}
+
+#[test]
+fn structures_stand_on_the_heightmap() {
+    // ровное плато высотой 40 м на 512x512 м
+    let plateau = Heightmap::new(4, 4, vec![1.; 16]);
+    let settings = HeightmapSettings {
+        extents: [512., 512.],
+        vertical_scale: 40.,
+        ..settings(HeightmapSampling::Bilinear)
+    };
+    let config = WorldConfig {
+        structures: vec![StructureSettings {
+            name: "boulder".to_string(),
+            kind: StructureKind::Prop,
+            spacing: 24.,
+            chance: 0.7,
+            max_slope: 0.5,
+            biomes: Vec::new(),
+            avoid_water: false,
+            radius: 6.,
+        }],
+        ..Default::default()
+    };
+    let world_density = WorldDensity::from_base(
+        &config,
+        Arc::new(HeightmapTerrain::new(plateau, settings, None)),
+    );
+    let placements = world_density
+        .structures()
+        .expect("structures from the config should be placed")
+        .placements_in([64., 64.], [448., 448.]);
+    assert!(!placements.is_empty());
+    for placement in placements {
+        assert!((placement.position[1] - 40.).abs() < 1e-6);
+    }
+}
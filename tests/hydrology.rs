@@ -0,0 +1,118 @@
+use std::sync::Arc;
+
+use bevy_voxel_engine::{
+    DensityFunction, HydrologySettings, HydrologyTerrain, ResolutionOfTheGrid, VoxelChunk,
+};
+
+// рельеф, заданный поверхностью height(x, z)
+struct Heightfield<F>(F);
+
+impl<F> DensityFunction for Heightfield<F>
+where
+    F: Fn(f64, f64) -> f64 + Send + Sync,
+{
+    fn sample(&self, position: [f64; 3]) -> f64 {
+        (self.0)(position[0], position[2]) - position[1]
+    }
+
+    fn surface_height(&self, position: [f64; 2]) -> Option<f64> {
+        Some((self.0)(position[0], position[1]))
+    }
+}
+
+// гребни на |z| = 200: склоны между долинами конечны, и бесконечный склон
+// не собирает воду со всей плоскости
+fn ridges(z: f64) -> f64 {
+    0.2 * (200. - (z.abs() - 200.).abs())
+}
+
+fn hydrology(
+    height: impl Fn(f64, f64) -> f64 + Send + Sync + 'static,
+    sea_level: f64,
+) -> HydrologyTerrain {
+    HydrologyTerrain::new(
+        Arc::new(Heightfield(height)),
+        HydrologySettings {
+            sea_level,
+            tile_cells: 64,
+            cache_capacity: 8,
+            ..Default::default()
+        },
+    )
+}
+
+#[test]
+fn sea_covers_terrain_below_sea_level() {
+    // берег у x = 0, водораздел у x = 200
+    let terrain = hydrology(|x, _| x.min(400. - x) * 0.1, 0.);
+    assert_eq!(terrain.water_level([-100., 20.]), Some(0.));
+    assert_eq!(terrain.water_level([100., 20.]), None);
+}
+
+#[test]
+fn basins_fill_into_lakes() {
+    // впадина глубиной 20 м посреди равнины, за кольцом r = 200 склон вниз
+    let terrain = hydrology(
+        |x, z| {
+            let radius = x.hypot(z);
+            20. - 20. * (-(radius * radius) / (60. * 60.)).exp() - (radius - 200.).max(0.) * 0.05
+        },
+        -100.,
+    );
+    let ground = terrain.surface_height([0., 0.]).unwrap();
+    let level = terrain
+        .water_level([0., 0.])
+        .expect("basin should hold a lake");
+    assert!(
+        level - ground > 15.,
+        "lake is only {} m deep",
+        level - ground
+    );
+}
+
+#[test]
+fn rivers_carve_valley_floors() {
+    // долина вдоль оси x, спускающаяся к морю
+    let terrain = hydrology(|x, z| x * 0.05 + ridges(z), -1000.);
+    let position = [40., 0.];
+    let ground = terrain.surface_height(position).unwrap();
+    assert!(ground < 40. * 0.05 - 0.5, "valley floor is not carved");
+    assert!(terrain.water_level(position).is_some());
+    assert_eq!(terrain.water_level([40., 150.]), None);
+}
+
+#[test]
+fn chunks_expose_water_volume_separately() {
+    let terrain = hydrology(|x, _| x.min(400. - x) * 0.1 - 20., 0.);
+    let resolution = ResolutionOfTheGrid::new(9);
+    // чанк над морем у берега и чанк высоко в воздухе
+    let mut sea_chunk = VoxelChunk::new([0, -1, 0]);
+    assert!(sea_chunk.sample_water_grid(&terrain, resolution).is_some());
+    assert!(sea_chunk.return_water_mesh(&terrain, resolution).is_some());
+    let sky_chunk = VoxelChunk::new([0, 5, 0]);
+    assert!(sky_chunk.sample_water_grid(&terrain, resolution).is_none());
+}
+
+#[test]
+fn rivers_cross_tiles_and_reach_the_sea() {
+    // долина вдоль оси x спускается к морю у x = 400; тайлы по 256 м
+    let terrain = hydrology(|x, z| -x * 0.05 + ridges(z), -20.);
+    let mut previous_depth = None;
+    for step in 0..150 {
+        let x = -200. + step as f64 * 4.;
+        let ground = terrain.surface_height([x, 0.]).unwrap();
+        let level = terrain
+            .water_level([x, 0.])
+            .unwrap_or_else(|| panic!("river ends at x = {x}"));
+        let depth = level - ground;
+        assert!(depth > 0.5, "river is only {depth} m deep at x = {x}");
+        // на стыках тайлов (x = 0 и x = 256) русло не мелеет; у устья оно
+        // сливается с морем
+        if let (Some(previous), true) = (previous_depth, x < 380.) {
+            assert!(depth >= previous - 1e-6, "river shallows at x = {x}");
+        }
+        previous_depth = Some(depth);
+    }
+    // за устьем - море
+    assert_eq!(terrain.water_level([420., 0.]), Some(-20.));
+}
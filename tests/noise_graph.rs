@@ -0,0 +1,40 @@
+use bevy_voxel_engine::{DensityFunction, NoiseGraph, NoiseGraphOutput, TerrainScale, WorldSeed};
+
+const HILLS: &str = include_str!("../assets/terrain/hills.noise.ron");
+
+#[test]
+fn example_graph_parses_and_builds() {
+    let graph = NoiseGraph::from_ron(HILLS).expect("example graph should parse");
+    assert_eq!(graph.output, NoiseGraphOutput::Height);
+
+    let density = graph.build(WorldSeed::new(3), TerrainScale::default());
+    // далеко под поверхностью - земля, далеко над ней - воздух
+    assert!(density.sample([10.5, -100., -4.25]) > 0.);
+    assert!(density.sample([10.5, 100., -4.25]) < 0.);
+}
+
+#[test]
+fn graph_is_deterministic_for_a_seed() {
+    let graph = NoiseGraph::from_ron(HILLS).unwrap();
+    let first = graph.build(WorldSeed::new(11), TerrainScale::default());
+    let second = graph.build(WorldSeed::new(11), TerrainScale::default());
+    for point in [[0.3, 0.1, 0.7], [-5.5, 0.4, 12.25], [100.1, -0.2, -3.3]] {
+        assert_eq!(
+            first.sample(point).to_bits(),
+            second.sample(point).to_bits()
+        );
+    }
+}
+
+#[test]
+fn density_output_samples_in_three_dimensions() {
+    let graph = NoiseGraph::from_ron(
+        "(output: Density, root: Add(SuperSimplex(layer: \"a\"), Constant(0.25)))",
+    )
+    .unwrap();
+    let density = graph.build(WorldSeed::default(), TerrainScale::default());
+    assert_ne!(
+        density.sample([0.5, 0.2, 0.5]),
+        density.sample([0.5, 16.0, 0.5])
+    );
+}
@@ -0,0 +1,46 @@
+use bevy::render::mesh::{Mesh, VertexAttributeValues};
+use bevy_voxel_engine::{ResolutionOfTheGrid, VoxelChunk, WorldConfig, WorldDensity, WorldSeed};
+
+fn attribute(mesh: &Mesh, id: bevy::render::mesh::MeshVertexAttribute) -> Vec<u32> {
+    match mesh.attribute(id).unwrap() {
+        VertexAttributeValues::Float32x3(values) => values
+            .iter()
+            .flatten()
+            .map(|value| value.to_bits())
+            .collect(),
+        VertexAttributeValues::Float32x4(values) => values
+            .iter()
+            .flatten()
+            .map(|value| value.to_bits())
+            .collect(),
+        _ => panic!("unexpected attribute format"),
+    }
+}
+
+#[test]
+fn parallel_chunk_matches_serial_byte_for_byte() {
+    let density = WorldDensity::from_config(&WorldConfig {
+        seed: WorldSeed::new(9),
+        ..WorldConfig::full()
+    });
+    // чанк на поверхности, чтобы меш был не пустым
+    let chunk = VoxelChunk::new([1, 0, 2]);
+    for resolution in [17, 33] {
+        let resolution = ResolutionOfTheGrid::new(resolution);
+        let serial_grid = chunk.sample_voxel_grid(density.function(), resolution);
+        let parallel_grid = chunk.sample_voxel_grid_parallel(density.function(), resolution);
+        let bits = |data: &[f32]| data.iter().map(|value| value.to_bits()).collect::<Vec<_>>();
+        assert_eq!(bits(serial_grid.data()), bits(parallel_grid.data()));
+
+        let serial = VoxelChunk::new([1, 0, 2]).return_chunk_mesh(density.function(), resolution);
+        let parallel = chunk.return_chunk_mesh_parallel(density.function(), resolution);
+        assert!(serial.count_vertices() > 0);
+        for id in [
+            Mesh::ATTRIBUTE_POSITION,
+            Mesh::ATTRIBUTE_NORMAL,
+            Mesh::ATTRIBUTE_COLOR,
+        ] {
+            assert_eq!(attribute(&serial, id.clone()), attribute(&parallel, id));
+        }
+    }
+}
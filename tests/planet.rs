@@ -0,0 +1,72 @@
+use bevy_voxel_engine::{
+    DensityFunction, PlanetSettings, PlanetTerrain, VoxelChunk, WorldConfig, WorldDensity,
+    WorldSeed,
+};
+
+fn planet() -> PlanetTerrain {
+    PlanetTerrain::new(
+        WorldSeed::new(8),
+        PlanetSettings {
+            centre: [100., -500., 40.],
+            radius: 500.,
+            displacement: 20.,
+            noise_scale: 128.,
+        },
+    )
+}
+
+#[test]
+fn density_is_radial() {
+    let planet = planet();
+    // в любую сторону от центра: внутри земля, снаружи воздух
+    for direction in [[1., 0., 0.], [0., -1., 0.], [0.6, 0., -0.8], [0., 1., 0.]] {
+        let at = |distance: f64| {
+            [
+                100. + direction[0] * distance,
+                -500. + direction[1] * distance,
+                40. + direction[2] * distance,
+            ]
+        };
+        assert!(planet.sample(at(470.)) > 0.);
+        assert!(planet.sample(at(530.)) < 0.);
+        let surface = planet.surface_radius(direction);
+        assert!((planet.sample(at(surface))).abs() < 1e-9);
+        assert!((planet.depth(at(surface - 7.)).unwrap() - 7.).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn up_points_away_from_the_centre() {
+    let planet = planet();
+    let up = planet.up([100., -500., 640.]);
+    assert!((up[2] - 1.).abs() < 1e-12 && up[0].abs() < 1e-12 && up[1].abs() < 1e-12);
+
+    let density = WorldDensity::from_config(&WorldConfig {
+        planet: Some(PlanetSettings::default()),
+        ..WorldConfig::with_seed(8)
+    });
+    let up = density.up([0., -1000., -1200.]);
+    assert!((up.z + 1.).abs() < 1e-6);
+    // плоский мир всегда смотрит вверх по y
+    let flat = WorldDensity::from_config(&WorldConfig::with_seed(8));
+    assert_eq!(flat.up([0., -1000., -1200.]), bevy::prelude::Vec3::Y);
+}
+
+#[test]
+fn shell_chunks_cover_the_surface() {
+    let settings = PlanetSettings::default();
+    let chunks = settings.shell_chunks();
+    let planet = PlanetTerrain::new(WorldSeed::new(8), settings.clone());
+    let size = VoxelChunk::size_chunk() as f64;
+    // чанки вокруг точек поверхности во всех направлениях есть в списке
+    for direction in [[0., 1., 0.], [0., -1., 0.], [0.8, 0., 0.6], [-0.6, 0.8, 0.]] {
+        let radius = planet.surface_radius(direction);
+        let point = [0, 1, 2].map(|axis| settings.centre[axis] + direction[axis] * radius);
+        let coordinates = point.map(|value| (value / size).floor() as i128);
+        assert!(chunks.contains(&coordinates));
+    }
+    // центр планеты и пространство далеко снаружи не загружаются
+    let centre = settings.centre.map(|value| (value / size).floor() as i128);
+    assert!(!chunks.contains(&centre));
+    assert!(!chunks.contains(&[0, 40, 0]));
+}
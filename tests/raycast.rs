@@ -0,0 +1,57 @@
+use bevy_voxel_engine::{
+    raycast, Brush, BrushOperation, DensityFunction, SculptBrush, SculptTool, VoxelEdits,
+    VoxelMaterial,
+};
+
+// ровная земля на y = 0
+fn flat(position: [f64; 3]) -> f64 {
+    -position[1]
+}
+
+// земля на y = 10, внутри чанков, а не на их границе
+fn raised(position: [f64; 3]) -> f64 {
+    10. - position[1]
+}
+
+#[test]
+fn ray_finds_the_ground_and_its_normal() {
+    let hit = raycast(&raised, [10.3, 30., -7.6], [1., -2., 0.5], 100.).unwrap();
+    assert!((hit.position[1] - 10.).abs() < 1e-6);
+    // точка лежит на луче
+    assert!((hit.position[0] - 20.3).abs() < 1e-5);
+    assert!((hit.position[2] + 2.6).abs() < 1e-5);
+    assert!((hit.normal[1] - 1.).abs() < 1e-9);
+    assert_eq!(hit.chunk, [0, 0, -1]);
+    assert_eq!(hit.material, raised.material(hit.position));
+
+    // вверх и слишком коротким лучом земли не найти
+    assert!(raycast(&flat, [0., 5., 0.], [0., 1., 0.], 100.).is_none());
+    assert!(raycast(&flat, [0., 5., 0.], [0., -1., 0.], 4.).is_none());
+}
+
+#[test]
+fn ray_sees_edits_and_paint() {
+    let mut edits = VoxelEdits::default();
+    edits.apply_brush(
+        &flat,
+        &Brush::sphere([0., 0., 0.], 6.),
+        BrushOperation::Remove,
+        1.,
+    );
+    // луч уходит в яму глубже исходной поверхности
+    let hit = edits
+        .raycast(&flat, [0.2, 10., 0.3], [0., -1., 0.], 50.)
+        .unwrap();
+    assert!(hit.position[1] < -4.);
+    assert!(hit.normal[1] > 0.9);
+
+    edits.sculpt(
+        &flat,
+        &SculptBrush::new([30., 0., 30.], 3.),
+        &SculptTool::Paint(VoxelMaterial::Gold),
+    );
+    let hit = edits
+        .raycast(&flat, [30., 4., 30.], [0., -1., 0.], 10.)
+        .unwrap();
+    assert_eq!(hit.material, VoxelMaterial::Gold);
+}
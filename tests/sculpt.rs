@@ -0,0 +1,120 @@
+use bevy_voxel_engine::{
+    DensityFunction, Falloff, SculptBrush, SculptTool, VoxelEdits, VoxelMaterial,
+};
+
+// ровная земля на y = 0
+fn flat(position: [f64; 3]) -> f64 {
+    -position[1]
+}
+
+// ступенька: справа от x = 0 земля на 4 м выше
+fn step(position: [f64; 3]) -> f64 {
+    if position[0] < 0. {
+        -position[1]
+    } else {
+        4. - position[1]
+    }
+}
+
+#[test]
+fn smoothing_softens_a_step_without_seams() {
+    let mut edits = VoxelEdits::default();
+    let brush = SculptBrush::new([0., 2., 5.], 6.).with_falloff(Falloff::Constant);
+    let touched = edits.sculpt(&step, &brush, &SculptTool::Smooth);
+    assert!(touched.contains(&[0, 0, 0]) && touched.contains(&[-1, 0, 0]));
+    // у ступеньки перепад плотности между соседями уменьшился
+    let before = step([0., 2., 5.]) - step([-1., 2., 5.]);
+    let after = edits.value_at(&step, [0, 2, 5]) - edits.value_at(&step, [-1, 2, 5]);
+    assert!((after as f64) < before);
+
+    // общие точки чанков по x = 0 совпадают
+    let left = edits.grid(&step, [-1, 0, 0]).unwrap();
+    let right = edits.grid(&step, [0, 0, 0]).unwrap();
+    for z in 0..VoxelEdits::resolution() {
+        for y in 0..VoxelEdits::resolution() {
+            assert_eq!(left.read(32, y, z), right.read(0, y, z));
+        }
+    }
+}
+
+#[test]
+fn level_and_flatten_pull_towards_the_plane() {
+    let mut edits = VoxelEdits::default();
+    edits.sculpt(
+        &flat,
+        &SculptBrush::new([10., 0., 10.], 5.).with_falloff(Falloff::Constant),
+        &SculptTool::Level { height: 3. },
+    );
+    // в центре поверхность поднялась до высоты 3
+    assert_eq!(edits.value_at(&flat, [10, 3, 10]), 0.);
+    // вне сферы ничего не меняется
+    assert_eq!(edits.value_at(&flat, [10, 3, 20]), -3.);
+
+    let mut edits = VoxelEdits::default();
+    let brush = SculptBrush::new([10., 0., 10.], 5.).with_strength(0.5);
+    edits.sculpt(
+        &flat,
+        &brush,
+        &SculptTool::Flatten {
+            normal: [1., 1., 0.],
+        },
+    );
+    // наклонная плоскость проходит через центр кисти, там земля не меняется
+    assert_eq!(edits.value_at(&flat, [10, 0, 10]), 0.);
+    // правее центра плоскость ниже земли, и плотность сдвинулась к ней
+    let value = edits.value_at(&flat, [11, 0, 10]);
+    assert!(value < 0. && value > -1.);
+}
+
+#[test]
+fn paint_changes_material_but_not_shape() {
+    let mut edits = VoxelEdits::default();
+    let brush = SculptBrush::new([5., 0., 5.], 3.);
+    let touched = edits.sculpt(&flat, &brush, &SculptTool::Paint(VoxelMaterial::Gold));
+    assert_eq!(touched, vec![[0, -1, 0], [0, 0, 0]]);
+    assert!(edits.delta([0, 0, 0]).unwrap().density.is_empty());
+    assert!(edits.is_dirty([0, 0, 0]));
+
+    let edited = edits.over(&flat);
+    assert_eq!(edited.material([5.2, 0.1, 4.9]), VoxelMaterial::Gold);
+    assert_eq!(edited.material([15., 0., 5.]), flat.material([15., 0., 5.]));
+    assert_eq!(edited.sample([5.5, 0.25, 5.]), flat.sample([5.5, 0.25, 5.]));
+}
+
+#[test]
+fn noise_without_scale_changes_nothing() {
+    let mut edits = VoxelEdits::default();
+    let brush = SculptBrush::new([0., 0., 0.], 4.);
+    for scale in [0., -1., f64::NAN] {
+        let tool = SculptTool::Noise {
+            amplitude: 1.,
+            scale,
+            seed: 7,
+        };
+        assert!(edits.sculpt(&flat, &brush, &tool).is_empty());
+    }
+    assert!(edits.edited_chunks().next().is_none());
+}
+
+#[test]
+fn weak_paint_strokes_add_up_to_full_coverage() {
+    let mut edits = VoxelEdits::default();
+    let brush = SculptBrush::new([5., 0., 5.], 2.)
+        .with_falloff(Falloff::Constant)
+        .with_strength(0.3);
+    let tool = SculptTool::Paint(VoxelMaterial::Gold);
+    edits.sculpt(&flat, &brush, &tool);
+    let painted = |edits: &VoxelEdits| {
+        (3..=7)
+            .flat_map(|z| (-2..=2).flat_map(move |y| (3..=7).map(move |x| [x, y, z])))
+            .filter(|point| edits.painted_material(*point).is_some())
+            .count()
+    };
+    let first = painted(&edits);
+    for _ in 0..40 {
+        edits.sculpt(&flat, &brush, &tool);
+    }
+    // в сфере радиусом 2 м 33 точки решетки, все они закрашены
+    assert!(first < 33);
+    assert_eq!(painted(&edits), 33);
+}
@@ -0,0 +1,117 @@
+use std::sync::Arc;
+
+use bevy_voxel_engine::{
+    Capsule, CsgOperation, Cuboid, Cylinder, DensityFunction, Plane, Sdf, SdfDensity, SdfTerrain,
+    Sphere, Torus, VoxelMaterial,
+};
+
+fn assert_distance(shape: &impl Sdf, position: [f64; 3], expected: f64) {
+    let distance = shape.distance(position);
+    assert!(
+        (distance - expected).abs() < 1e-9,
+        "distance at {position:?} is {distance}, expected {expected}"
+    );
+}
+
+fn sphere(center: [f64; 3], radius: f64) -> Sphere {
+    Sphere { center, radius }
+}
+
+#[test]
+fn primitive_distances() {
+    let sphere = sphere([1., 2., 3.], 2.);
+    assert_distance(&sphere, [1., 2., 3.], -2.);
+    assert_distance(&sphere, [1., 7., 3.], 3.);
+
+    let cuboid = Cuboid {
+        center: [0., 0., 0.],
+        half_extents: [1., 2., 3.],
+    };
+    assert_distance(&cuboid, [0., 0., 0.], -1.);
+    assert_distance(&cuboid, [4., 0., 0.], 3.);
+    // до угла расстояние считается по диагонали
+    assert_distance(&cuboid, [4., 6., 0.], 5.);
+
+    let capsule = Capsule {
+        start: [0., 0., 0.],
+        end: [0., 10., 0.],
+        radius: 1.,
+    };
+    assert_distance(&capsule, [3., 5., 0.], 2.);
+    assert_distance(&capsule, [0., 13., 0.], 2.);
+
+    let cylinder = Cylinder {
+        center: [0., 0., 0.],
+        radius: 2.,
+        half_height: 3.,
+    };
+    assert_distance(&cylinder, [0., 0., 0.], -2.);
+    assert_distance(&cylinder, [5., 0., 0.], 3.);
+    assert_distance(&cylinder, [0., 7., 0.], 4.);
+
+    let torus = Torus {
+        center: [0., 0., 0.],
+        major_radius: 5.,
+        minor_radius: 1.,
+    };
+    assert_distance(&torus, [5., 0., 0.], -1.);
+    assert_distance(&torus, [0., 0., 0.], 4.);
+    assert_distance(&torus, [0., 3., 5.], 2.);
+
+    let plane = Plane::new([0., 2., 0.], 1.);
+    assert_eq!(plane.normal(), [0., 1., 0.]);
+    assert_distance(&plane, [7., 4., -3.], 3.);
+    assert_distance(&plane, [0., -1., 0.], -2.);
+}
+
+#[test]
+#[should_panic(expected = "non-zero normal")]
+fn plane_rejects_a_zero_normal() {
+    Plane::new([0., 0., 0.], 1.);
+}
+
+#[test]
+fn csg_operations_combine_distances() {
+    let point = [0., 0., 0.];
+    let a = || sphere([-1., 0., 0.], 2.);
+    let b = || sphere([2., 0., 0.], 1.);
+    assert_distance(&a().union(b()), point, -1.);
+    assert_distance(&a().intersect(b()), point, 1.);
+    assert_distance(&a().subtract(b()), point, -1.);
+    assert_distance(&b().subtract(a()), [2., 0., 0.], -1.);
+    assert_distance(&a().translate([10., 0., 0.]), [9., 0., 0.], -2.);
+
+    // плавное объединение не больше обычного и совпадает с ним вдали от стыка
+    let smooth = a().smooth_union(b(), 1.);
+    assert!(smooth.distance([1., 0., 0.]) < a().union(b()).distance([1., 0., 0.]));
+    assert_distance(&smooth, [-1., 0., 0.], -2.);
+    assert_eq!(CsgOperation::SmoothUnion(0.).apply(3., 5.), 3.);
+}
+
+#[test]
+fn shapes_become_density() {
+    let density = SdfDensity::new(sphere([0., 0., 0.], 4.), VoxelMaterial::Rock);
+    assert_eq!(density.sample([0., 1., 0.]), 3.);
+    assert_eq!(density.material([0., 0., 0.]), VoxelMaterial::Rock);
+
+    // арка над плоской равниной и выемка в ней
+    let ground: Arc<dyn DensityFunction> = Arc::new(|position: [f64; 3]| -position[1]);
+    let arch = SdfTerrain::new(
+        ground.clone(),
+        sphere([0., 20., 0.], 5.),
+        CsgOperation::Union,
+        VoxelMaterial::Rock,
+    );
+    assert!(arch.sample([0., 20., 0.]) > 0.);
+    assert_eq!(arch.material([0., 25., 0.]), VoxelMaterial::Rock);
+    assert!(arch.sample([30., -1., 0.]) > 0.);
+
+    let pit = SdfTerrain::new(
+        ground,
+        sphere([0., 0., 0.], 5.),
+        CsgOperation::Subtraction,
+        VoxelMaterial::Rock,
+    );
+    assert!(pit.sample([0., -3., 0.]) < 0.);
+    assert!(pit.sample([0., -6., 0.]) > 0.);
+}
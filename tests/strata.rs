@@ -0,0 +1,88 @@
+use std::sync::Arc;
+
+use bevy_voxel_engine::{
+    DensityFunction, OreSettings, SubsurfaceMaterials, SubsurfaceSettings, VoxelMaterial,
+    WorldConfig, WorldDensity, WorldSeed,
+};
+
+// ровная земля на y = 0 с травой на поверхности
+struct Flat;
+
+impl DensityFunction for Flat {
+    fn sample(&self, position: [f64; 3]) -> f64 {
+        -position[1]
+    }
+
+    fn material(&self, _position: [f64; 3]) -> VoxelMaterial {
+        VoxelMaterial::Grass
+    }
+
+    fn surface_height(&self, _position: [f64; 2]) -> Option<f64> {
+        Some(0.)
+    }
+}
+
+fn subsurface(ores: Vec<OreSettings>) -> SubsurfaceMaterials {
+    SubsurfaceMaterials::new(
+        Arc::new(Flat),
+        WorldSeed::new(11),
+        SubsurfaceSettings {
+            ores,
+            ..Default::default()
+        },
+    )
+}
+
+#[test]
+fn strata_follow_depth() {
+    let subsurface = subsurface(Vec::new());
+    assert_eq!(subsurface.material([5., -0.5, 5.]), VoxelMaterial::Grass);
+    assert_eq!(subsurface.material([5., -2.5, 5.]), VoxelMaterial::Dirt);
+    assert_eq!(subsurface.material([5., -40., 5.]), VoxelMaterial::Rock);
+    assert_eq!(subsurface.material([5., -500., 5.]), VoxelMaterial::Basalt);
+    // форма рельефа не меняется
+    assert_eq!(subsurface.sample([5., -40., 5.]), 40.);
+}
+
+#[test]
+fn ores_respect_rarity_and_depth_range() {
+    let ore = |rarity| OreSettings {
+        material: VoxelMaterial::Iron,
+        rarity,
+        min_depth: 20.,
+        max_depth: 60.,
+        cluster_size: 4.,
+    };
+    let share = |subsurface: &SubsurfaceMaterials, y: f64| {
+        let mut count = 0;
+        for z in 0..60 {
+            for x in 0..60 {
+                let position = [x as f64 * 1.7, y, z as f64 * 1.3];
+                if subsurface.material(position) == VoxelMaterial::Iron {
+                    count += 1;
+                }
+            }
+        }
+        count as f64 / 3600.
+    };
+    let rare = subsurface(vec![ore(0.05)]);
+    let common = subsurface(vec![ore(0.3)]);
+    assert!(share(&rare, -40.) > 0.005 && share(&rare, -40.) < 0.15);
+    assert!(share(&common, -40.) > share(&rare, -40.) * 2.);
+    assert_eq!(share(&common, -10.), 0.);
+    assert_eq!(share(&common, -80.), 0.);
+}
+
+#[test]
+fn world_density_reports_materials_below_the_surface() {
+    let density = WorldDensity::from_config(&WorldConfig {
+        seed: WorldSeed::new(3),
+        ..WorldConfig::full()
+    });
+    let surface = density.function().surface_height([100., 100.]).unwrap();
+    let deep = density.material([100., surface - 300., 100.]);
+    assert!(matches!(
+        deep,
+        VoxelMaterial::Basalt | VoxelMaterial::Gold | VoxelMaterial::Iron
+    ));
+}
@@ -0,0 +1,135 @@
+use std::sync::Arc;
+
+use bevy_voxel_engine::{
+    Biome, BiomeMap, CsgOperation, DensityFunction, Sphere, StructureKind, StructurePlacement,
+    StructureSettings, StructureTerrain, VoxelMaterial, WorldSeed,
+};
+
+// поверхность с заданным уклоном вдоль x
+struct Slope(f64);
+
+impl DensityFunction for Slope {
+    fn sample(&self, position: [f64; 3]) -> f64 {
+        position[0] * self.0 - position[1]
+    }
+
+    fn surface_height(&self, position: [f64; 2]) -> Option<f64> {
+        Some(position[0] * self.0)
+    }
+}
+
+fn settings(kind: StructureKind) -> StructureSettings {
+    StructureSettings {
+        name: "boulder".to_string(),
+        kind,
+        spacing: 24.,
+        chance: 0.7,
+        max_slope: 0.5,
+        biomes: Vec::new(),
+        avoid_water: true,
+        radius: 6.,
+    }
+}
+
+fn placement(slope: f64, structure: StructureSettings) -> StructurePlacement {
+    StructurePlacement::new(
+        Arc::new(Slope(slope)),
+        WorldSeed::new(21),
+        512.,
+        vec![structure],
+    )
+}
+
+#[test]
+fn placement_does_not_depend_on_query_order() {
+    let whole = placement(0.1, settings(StructureKind::Prop)).placements_in([0., 0.], [512., 512.]);
+    assert!(whole.len() > 50);
+
+    // другой экземпляр, другие окна запросов в обратном порядке
+    let other = placement(0.1, settings(StructureKind::Prop));
+    let mut pieces = Vec::new();
+    for z in (0..16).rev() {
+        for x in (0..16).rev() {
+            let min = [x as f64 * 32., z as f64 * 32.];
+            pieces.extend(other.placements_in(min, [min[0] + 32., min[1] + 32.]));
+        }
+    }
+    assert_eq!(pieces.len(), whole.len());
+    for placement in &whole {
+        assert!(pieces.contains(placement));
+    }
+}
+
+#[test]
+fn placements_keep_their_spacing() {
+    let placements =
+        placement(0.1, settings(StructureKind::Prop)).placements_in([-300., -300.], [300., 300.]);
+    for (index, first) in placements.iter().enumerate() {
+        for second in &placements[index + 1..] {
+            let distance = (first.position[0] - second.position[0])
+                .hypot(first.position[2] - second.position[2]);
+            assert!(distance >= 24., "structures are {distance} m apart");
+        }
+    }
+}
+
+#[test]
+fn slope_and_biome_rules_filter_placements() {
+    let steep = placement(2., settings(StructureKind::Prop));
+    assert!(steep.placements_in([0., 0.], [512., 512.]).is_empty());
+
+    let desert_only = placement(
+        0.,
+        StructureSettings {
+            biomes: vec![Biome::Desert],
+            ..settings(StructureKind::Prop)
+        },
+    );
+    let biome_map = BiomeMap::new(WorldSeed::new(21), 512.);
+    let placements = desert_only.placements_in([-4000., -4000.], [4000., 4000.]);
+    assert!(!placements.is_empty());
+    for placement in placements {
+        assert_eq!(
+            biome_map.biome_at([placement.position[0], placement.position[2]]),
+            Biome::Desert
+        );
+    }
+}
+
+#[test]
+fn stamps_are_written_into_density() {
+    let stamp = StructureKind::Stamp {
+        shape: Arc::new(Sphere {
+            center: [0., 3., 0.],
+            radius: 3.,
+        }),
+        operation: CsgOperation::Union,
+        material: VoxelMaterial::Rock,
+    };
+    let placement = Arc::new(placement(0., settings(stamp)));
+    let first = placement.placements_in([0., 0.], [512., 512.])[0].clone();
+    let terrain = StructureTerrain::new(Arc::new(Slope(0.)), placement);
+    let inside = [first.position[0], 3., first.position[2]];
+    assert!(terrain.sample(inside) > 0.);
+    assert_eq!(terrain.material(inside), VoxelMaterial::Rock);
+    // вдали от построек рельеф не меняется
+    assert_eq!(
+        terrain.sample([first.position[0], 3., first.position[2] + 12.]),
+        -3.
+    );
+}
+
+#[test]
+fn placements_carry_the_surface_normal() {
+    let placements =
+        placement(0.3, settings(StructureKind::Prop)).placements_in([0., 0.], [256., 256.]);
+    assert!(!placements.is_empty());
+    // уклон 0.3 вдоль x: нормаль наклонена против подъема
+    let length = (0.3f64 * 0.3 + 1.).sqrt();
+    for placement in placements {
+        let [x, y, z] = placement.normal;
+        assert!((x + 0.3 / length).abs() < 1e-9);
+        assert!((y - 1. / length).abs() < 1e-9);
+        assert!(z.abs() < 1e-9);
+    }
+}
@@ -0,0 +1,71 @@
+use std::sync::Arc;
+
+use bevy_voxel_engine::{
+    DensityFunction, DomainWarpSettings, FeatureTerrain, FloatingIslandSettings, OverhangSettings,
+    TerrainFeatures, WorldSeed,
+};
+
+// ровная поверхность на высоте 0
+struct Flat;
+
+impl DensityFunction for Flat {
+    fn sample(&self, position: [f64; 3]) -> f64 {
+        -position[1]
+    }
+
+    fn surface_height(&self, _position: [f64; 2]) -> Option<f64> {
+        Some(0.)
+    }
+}
+
+fn terrain(features: TerrainFeatures) -> FeatureTerrain {
+    FeatureTerrain::new(Arc::new(Flat), WorldSeed::new(21), features)
+}
+
+// число переходов земля/воздух в столбце снизу вверх
+fn column_transitions(terrain: &FeatureTerrain, x: f64, z: f64) -> usize {
+    let solid: Vec<bool> = (-64..256)
+        .map(|y| terrain.sample([x, y as f64, z]) > 0.)
+        .collect();
+    solid.windows(2).filter(|pair| pair[0] != pair[1]).count()
+}
+
+#[test]
+fn domain_warp_moves_the_surface() {
+    let plain = terrain(TerrainFeatures::default());
+    let warped = terrain(TerrainFeatures {
+        domain_warp: Some(DomainWarpSettings::default()),
+        ..Default::default()
+    });
+    let point = [37.5, 0.5, -81.25];
+    assert_eq!(plain.warp(point), point);
+    assert_ne!(warped.warp(point), point);
+}
+
+#[test]
+fn overhangs_put_air_under_solid_ground() {
+    let terrain = terrain(TerrainFeatures {
+        overhangs: Some(OverhangSettings {
+            amplitude: 40.,
+            ..Default::default()
+        }),
+        ..Default::default()
+    });
+    let found = (0..64).any(|i| column_transitions(&terrain, i as f64 * 7., 3.) > 1);
+    assert!(found, "no column with an overhang found");
+}
+
+#[test]
+fn floating_islands_appear_above_the_surface() {
+    let settings = FloatingIslandSettings::default();
+    let altitude = settings.altitude;
+    let terrain = terrain(TerrainFeatures {
+        floating_islands: Some(settings),
+        ..Default::default()
+    });
+    let found = (0..64)
+        .flat_map(|z| (0..64).map(move |x| [x as f64 * 8., altitude, z as f64 * 8.]))
+        .any(|point| terrain.sample(point) > 0.);
+    assert!(found, "no floating island found");
+    assert!(terrain.sample([0., altitude * 3., 0.]) < 0.);
+}
@@ -0,0 +1,48 @@
+use bevy::prelude::*;
+use bevy_voxel_engine::{
+    ResolutionOfTheGrid, SdfDensity, Sphere, VoxelBody, VoxelChunk, VoxelMaterial,
+};
+
+// сплошная материя: все точки внутри
+fn solid(_position: [f64; 3]) -> f64 {
+    1.
+}
+
+#[test]
+fn density_is_clipped_by_bounds() {
+    let body = VoxelBody::new(solid, Vec3::new(-10., -10., -10.), Vec3::new(40., 10., 10.));
+    assert!(body.density().sample([0., 0., 0.]) > 0.);
+    assert!(body.density().sample([39., 0., 0.]) > 0.);
+    assert!(body.density().sample([41., 0., 0.]) < 0.);
+    assert!(body.density().sample([0., -12., 0.]) < 0.);
+
+    // чанки покрывают куб целиком
+    let chunks = body.chunks();
+    assert_eq!(chunks.len(), 3 * 2 * 2);
+    assert!(chunks.contains(&[-1, -1, -1]) && chunks.contains(&[1, 0, 0]));
+
+    // поверхность замыкается на границе, даже если плотность везде положительна
+    let mut chunk = VoxelChunk::new([1, 0, 0]);
+    let mesh = chunk.return_chunk_mesh(body.density(), ResolutionOfTheGrid::new(16));
+    assert!(mesh.count_vertices() > 0);
+}
+
+#[test]
+fn sampling_follows_the_body_transform() {
+    let asteroid = SdfDensity::new(
+        Sphere {
+            center: [0., 0., 10.],
+            radius: 4.,
+        },
+        VoxelMaterial::Rock,
+    );
+    let body = VoxelBody::new(asteroid, Vec3::splat(-20.), Vec3::splat(20.));
+    // тело сдвинуто на 100 по x и повернуто на 90 градусов вокруг y
+    let transform = GlobalTransform::from(
+        Transform::from_xyz(100., 0., 0.).with_rotation(Quat::from_rotation_y(90f32.to_radians())),
+    );
+    // локальная точка (0, 0, 10) после поворота оказывается в (10, 0, 0)
+    assert!(body.sample_at(&transform, Vec3::new(110., 0., 0.)) > 3.);
+    assert!(body.sample_at(&transform, Vec3::new(100., 0., 10.)) < 0.);
+    assert_eq!(body.density().material([0., 0., 10.]), VoxelMaterial::Rock);
+}
@@ -0,0 +1,58 @@
+use bevy_voxel_engine::{ResolutionOfTheGrid, VoxelChunk, WorldConfig, WorldDensity, WorldSeed};
+
+// FNV-1a по битам сетки настоящего рельефа мира (WorldDensity::from_config)
+fn world_grid_hash(seed: u64, chunk: [i128; 3]) -> u64 {
+    let density = WorldDensity::from_config(&WorldConfig {
+        seed: WorldSeed::new(seed),
+        ..Default::default()
+    });
+    VoxelChunk::new(chunk)
+        .sample_voxel_grid(density.function(), ResolutionOfTheGrid::new(17))
+        .data()
+        .iter()
+        .flat_map(|value| value.to_bits().to_le_bytes())
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+// эталонные значения: меняются только вместе с намеренной сменой генерации,
+// иначе старые сиды перестанут давать прежние миры
+#[test]
+fn seed_42_reproduces_the_pinned_world() {
+    let hashes: Vec<_> = [[0, 0, 0], [-3, 0, 7], [12, -1, -40]]
+        .iter()
+        .map(|chunk| world_grid_hash(42, *chunk))
+        .collect();
+    assert_eq!(
+        hashes,
+        vec![
+            0x4445_9856_8e28_b819,
+            0x1f43_9eab_0d87_d4f3,
+            0xd610_2a4e_379c_1cc1
+        ]
+    );
+    assert_eq!(
+        WorldSeed::new(42).sub_seed("surface").value(),
+        9_046_717_704_135_267_194
+    );
+}
+
+#[test]
+fn different_seeds_produce_different_worlds() {
+    let chunks = [[0, 0, 0], [1, 0, 0], [0, 0, 1], [-1, 0, -1]];
+    let first: Vec<_> = chunks.iter().map(|c| world_grid_hash(1, *c)).collect();
+    let second: Vec<_> = chunks.iter().map(|c| world_grid_hash(2, *c)).collect();
+    assert_ne!(first, second);
+}
+
+#[test]
+fn sub_seeds_are_independent_and_stable() {
+    let seed = WorldSeed::new(7);
+    assert_eq!(seed.sub_seed("surface"), seed.sub_seed("surface"));
+    assert_ne!(seed.sub_seed("surface"), seed.sub_seed("caves"));
+    assert_ne!(
+        seed.sub_seed("surface"),
+        WorldSeed::new(8).sub_seed("surface")
+    );
+}
@@ -0,0 +1,20 @@
+use std::sync::Mutex;
+
+use bevy_voxel_engine::{ResolutionOfTheGrid, VoxelChunk};
+
+#[test]
+fn density_receives_world_coordinates_in_metres() {
+    let size = VoxelChunk::size_chunk() as f64;
+    let positions = Mutex::new(Vec::new());
+    let recorder = |position: [f64; 3]| {
+        positions.lock().unwrap().push(position);
+        0.
+    };
+
+    VoxelChunk::new([1, -1, 2]).sample_voxel_grid(&recorder, ResolutionOfTheGrid::new(2));
+
+    let positions = positions.into_inner().unwrap();
+    assert_eq!(positions.len(), 8);
+    assert_eq!(positions.first(), Some(&[size, -size, 2. * size]));
+    assert_eq!(positions.last(), Some(&[2. * size, 0., 3. * size]));
+}